@@ -0,0 +1,258 @@
+use std::{env, fs, path::PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::status::{rgba, Rgba};
+
+/// Top-level configuration, loaded from
+/// `$XDG_CONFIG_HOME/sema/config.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Update interval in seconds.
+    pub refresh_rate: u32,
+    pub window: Window,
+    pub colors: Colors,
+
+    /// Columns of bars, from left to right.
+    pub columns: Vec<Column>,
+}
+
+/// Window geometry, in pixels.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Window {
+    /// Width of a single column.
+    pub bar_thickness: i32,
+
+    /// Height of the window, i.e. of a full bar.
+    pub bar_height: i32,
+
+    /// Distance from the bottom-right corner of the screen.
+    pub margin: i32,
+}
+
+/// The color palette shared by the indicators.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    #[serde(deserialize_with = "hex")]
+    pub urgent: Rgba,
+    #[serde(deserialize_with = "hex")]
+    pub warn: Rgba,
+    #[serde(deserialize_with = "hex")]
+    pub ok: Rgba,
+    #[serde(deserialize_with = "hex")]
+    pub bg: Rgba,
+    #[serde(deserialize_with = "hex")]
+    pub mute: Rgba,
+    #[serde(deserialize_with = "hex")]
+    pub normal: Rgba,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Column {
+    pub segments: Vec<Segment>,
+}
+
+/// A single indicator placed in a column.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Segment {
+    /// Name of the indicator to draw.
+    pub indicator: String,
+
+    /// Vertical offset from the bottom, as a percent of the window height.
+    #[serde(default)]
+    pub y: f64,
+
+    /// Maximum height of the segment, as a percent of the window height.
+    /// Indicators which report a level (e.g. battery) are scaled to it.
+    #[serde(default = "full_height")]
+    pub height: f64,
+
+    /// Any other keys are options for the indicator itself.
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+fn full_height() -> f64 {
+    1.0
+}
+
+impl Segment {
+    fn new(indicator: &str, y: f64, height: f64) -> Self {
+        Segment {
+            indicator: indicator.into(),
+            y,
+            height,
+            options: toml::Table::new(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            refresh_rate: 5,
+            window: Window::default(),
+            colors: Colors::default(),
+            columns: vec![
+                Column {
+                    segments: vec![
+                        Segment::new("mic", 0.80, 0.200),
+                        Segment::new("bluetooth", 0.60, 0.200),
+                        Segment::new("layout", 0.45, 0.125),
+                        Segment::new("wifi", 0.00, 0.400),
+                    ],
+                },
+                Column {
+                    segments: vec![Segment::new("volume", 0.0, 1.0)],
+                },
+                Column {
+                    segments: vec![Segment::new("battery", 0.0, 1.0)],
+                },
+            ],
+        }
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            bar_thickness: 2,
+            bar_height: 16,
+            margin: 2,
+        }
+    }
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            urgent: rgba(0xcf4955ff),
+            warn: rgba(0xfbc011ff),
+            ok: rgba(0x0a8c6cff),
+            bg: rgba(0x161616ff),
+            mute: rgba(0x777777ff),
+            normal: rgba(0x256ccfff),
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file, falling back to the defaults
+    /// if there isn't one.
+    pub fn load() -> Result<Config, String> {
+        let path = path();
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| err.to_string())
+                .and_then(|config: Config| config.validate().map(|()| config))
+                .map_err(|err| format!("Invalid config {}: {}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(format!("Failed to read config {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Check the values which can't be expressed by their types.
+    fn validate(&self) -> Result<(), String> {
+        if self.refresh_rate == 0 {
+            return Err("refresh_rate must be at least 1".into());
+        }
+        if self.window.bar_thickness <= 0 || self.window.bar_height <= 0 {
+            return Err("bar_thickness and bar_height must be positive".into());
+        }
+        let segments = self.columns.iter().flat_map(|column| &column.segments);
+        for segment in segments {
+            if !(0. ..=1.).contains(&segment.y) || !(0. ..=1.).contains(&segment.height) {
+                return Err(format!(
+                    "y and height of {} must be between 0 and 1",
+                    segment.indicator
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Colors {
+    /// Look up a color by its name in the palette,
+    /// or parse it as `#rrggbb` or `#rrggbbaa`.
+    pub fn resolve(&self, color: &str) -> Result<Rgba, String> {
+        match color {
+            "urgent" => Ok(self.urgent),
+            "warn" => Ok(self.warn),
+            "ok" => Ok(self.ok),
+            "bg" => Ok(self.bg),
+            "mute" => Ok(self.mute),
+            "normal" => Ok(self.normal),
+            _ => parse_hex(color).ok_or_else(|| format!("invalid color: {}", color)),
+        }
+    }
+}
+
+/// Location of the config file.
+fn path() -> PathBuf {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_default();
+    dir.join("sema").join("config.toml")
+}
+
+/// Deserialize a color written as `#rrggbb` or `#rrggbbaa`.
+fn hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgba, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_hex(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid color: {}", s)))
+}
+
+fn parse_hex(s: &str) -> Option<Rgba> {
+    let digits = s.trim_start_matches('#');
+    let color = u32::from_str_radix(digits, 16).ok()?;
+    match digits.len() {
+        6 => Some(rgba(color << 8 | 0xff)),
+        8 => Some(rgba(color)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(config: &str) -> Result<(), String> {
+        let config: Config = toml::from_str(config).map_err(|err| err.to_string())?;
+        config.validate()
+    }
+
+    #[test]
+    fn default_is_valid() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        assert_eq!(parse(""), Ok(()));
+    }
+
+    #[test]
+    fn rejects_zero_refresh_rate() {
+        assert!(parse("refresh_rate = 0").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_window() {
+        assert!(parse("[window]\nbar_thickness = 0").is_err());
+        assert!(parse("[window]\nbar_height = -1").is_err());
+    }
+
+    #[test]
+    fn rejects_segments_out_of_bounds() {
+        let segment = |segment: &str| parse(&format!("[[columns]]\nsegments = [{}]", segment));
+        assert_eq!(
+            segment(r#"{ indicator = "cpu", y = 0.5, height = 0.5 }"#),
+            Ok(())
+        );
+        assert!(segment(r#"{ indicator = "cpu", y = -0.1 }"#).is_err());
+        assert!(segment(r#"{ indicator = "cpu", y = 1.5 }"#).is_err());
+        assert!(segment(r#"{ indicator = "cpu", height = 2 }"#).is_err());
+    }
+}
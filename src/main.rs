@@ -1,31 +1,39 @@
-#![feature(lazy_cell)]
-#![feature(const_fn_floating_point_arithmetic)]
-
+mod config;
 mod status;
 
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, OnceLock},
+};
+
+use config::{Config, Window};
 use gdk::{
     cairo::{self, Context},
-    glib::timeout_add_seconds_local,
+    glib::{idle_add_once, timeout_add_seconds_local, SendWeakRef},
 };
 use gtk::{prelude::*, ApplicationWindow, DrawingArea};
 use gtk_layer_shell::{Edge, Layer, LayerShell};
+use status::{Placement, Redraw, Registry};
 
-/// Update interval in seconds.
-const REFRESH_RATE: u32 = 5;
+/// The drawing area, once the window has been set up.
+type Canvas = Arc<OnceLock<SendWeakRef<DrawingArea>>>;
 
-/// Number of bars and their thickness.
-const N_BARS: i32 = 3;
-const BAR_THICKNESS: i32 = 2;
-const BAR_HEIGHT: i32 = 16;
+fn setup(
+    app: &gtk::Application,
+    config: Rc<Config>,
+    registry: Rc<RefCell<Registry>>,
+    canvas: Canvas,
+) {
+    registry.borrow_mut().poll();
 
-const WIN_HEIGHT: i32 = BAR_HEIGHT;
-const WIN_WIDTH: i32 = N_BARS * BAR_THICKNESS;
+    let width = config.columns.len() as i32 * config.window.bar_thickness;
+    let height = config.window.bar_height;
 
-fn setup(app: &gtk::Application) {
     let win = ApplicationWindow::builder()
         .application(app)
-        .default_width(WIN_WIDTH)
-        .default_height(WIN_HEIGHT)
+        .default_width(width)
+        .default_height(height)
         .border_width(0)
         .app_paintable(true)
         .decorated(false)
@@ -42,21 +50,40 @@ fn setup(app: &gtk::Application) {
     // Anchor to bottom-right
     win.set_anchor(Edge::Right, true);
     win.set_anchor(Edge::Bottom, true);
-    win.set_layer_shell_margin(Edge::Right, 2);
-    win.set_layer_shell_margin(Edge::Bottom, 2);
+    win.set_layer_shell_margin(Edge::Right, config.window.margin);
+    win.set_layer_shell_margin(Edge::Bottom, config.window.margin);
 
     // Drawing the bars
     let drawing_area = DrawingArea::new();
     win.set_child(Some(&drawing_area));
-    drawing_area.set_size_request(WIN_WIDTH, WIN_HEIGHT);
-    drawing_area.connect_draw(|_, cr| {
-        if let Err(err) = draw(cr) {
-            eprintln!("{}", err);
-        }
+    drawing_area.set_size_request(width, height);
+    let _ = canvas.set(drawing_area.downgrade().into());
+    let draw_config = config.clone();
+    let draw_registry = registry.clone();
+    drawing_area.connect_draw(move |_, cr| {
+        draw(cr, &draw_config.window, &draw_registry.borrow());
         gtk::glib::Propagation::Stop
     });
 
-    timeout_add_seconds_local(REFRESH_RATE, move || {
+    // Details of the hovered indicator
+    drawing_area.set_has_tooltip(true);
+    let tooltip_config = config.clone();
+    let tooltip_registry = registry.clone();
+    drawing_area.connect_query_tooltip(move |_, x, y, _, tooltip| {
+        let window = &tooltip_config.window;
+        let column = (x / window.bar_thickness) as usize;
+        let y = 1. - y as f64 / window.bar_height as f64;
+        match tooltip_registry.borrow().details(column, y) {
+            Some(details) => {
+                tooltip.set_text(Some(&details));
+                true
+            }
+            None => false,
+        }
+    });
+
+    timeout_add_seconds_local(config.refresh_rate, move || {
+        registry.borrow_mut().poll();
         drawing_area.queue_draw();
         gdk::glib::ControlFlow::Continue
     });
@@ -64,41 +91,40 @@ fn setup(app: &gtk::Application) {
     win.show_all();
 }
 
-fn draw(cr: &Context) -> Result<(), String> {
+fn draw(cr: &Context, window: &Window, registry: &Registry) {
     // Transparent background
     cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
     cr.set_operator(cairo::Operator::Source);
     cr.paint().expect("Failed to paint");
 
     // Draw the bars
-    draw_bar(
-        cr,
-        2,
-        0.0,
-        status::battery().map_err(|_| "Failed to get battery info")?,
-    );
-    draw_bar(cr, 1, 0.0, status::volume()?);
-
-    draw_bar(cr, 0, 0.80, (0.200, status::mic()?));
-    draw_bar(cr, 0, 0.60, (0.200, status::bluetooth()?));
-    draw_bar(cr, 0, 0.45, (0.125, status::layout()?));
-    draw_bar(cr, 0, 0.00, (0.400, status::wifi()?));
-
-    Ok(())
+    for placement in registry.bars() {
+        draw_bar(cr, window, &placement);
+    }
 }
 
 /// Draw a single bar.
 ///
-/// * `col`: column to draw the bar in. Automatically adjusts for bar width.
-/// * `y`: y position to draw in as a percent of the window height.
-/// * `percent`: height of the bar as a percent of the window height.
+/// * `column`: column to draw the bar in. Automatically adjusts for bar width.
+/// * `y`, `height`: vertical position and maximum height of the bar.
+/// * `percent`: height of the bar as a percent of its maximum height.
 /// * `[r, g, b, a]`: decimal color to fill the bar with.
-fn draw_bar(cr: &Context, col: i32, y: f64, (percent, [r, g, b, a]): (f64, [f64; 4])) {
-    let filled = (WIN_HEIGHT as f64 * percent.min(1.)).floor();
+fn draw_bar(
+    cr: &Context,
+    window: &Window,
+    &Placement {
+        column,
+        y,
+        height: max_height,
+        bar: (percent, [r, g, b, a]),
+    }: &Placement,
+) {
+    let height = window.bar_height as f64;
+    let filled = (height * max_height * percent.min(1.)).floor();
     cr.rectangle(
-        (col * BAR_THICKNESS) as f64,
-        (1. - y) * WIN_HEIGHT as f64 - filled,
-        BAR_THICKNESS as f64 - 0.5, // Take off a bit for spacing
+        (column as i32 * window.bar_thickness) as f64,
+        (1. - y) * height - filled,
+        window.bar_thickness as f64 - 0.5, // Take off a bit for spacing
         filled,
     );
     cr.set_source_rgba(r, g, b, a);
@@ -110,6 +136,33 @@ fn main() {
         .application_id("anarres.utils.sema")
         .build();
 
-    application.connect_activate(setup);
+    // Indicators updated by events redraw from other threads,
+    // so hand the drawing area back to the main loop to do it.
+    let canvas: Canvas = Default::default();
+    let redraw: Redraw = {
+        let canvas = canvas.clone();
+        Arc::new(move || {
+            // Only touch the widget on the main thread.
+            let canvas = canvas.clone();
+            idle_add_once(move || {
+                if let Some(area) = canvas.get().and_then(|area| area.upgrade()) {
+                    area.queue_draw();
+                }
+            });
+        })
+    };
+
+    let (config, registry) = match Config::load().and_then(|config| {
+        let registry = Registry::new(&config, redraw)?;
+        Ok((config, registry))
+    }) {
+        Ok((config, registry)) => (Rc::new(config), Rc::new(RefCell::new(registry))),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    application
+        .connect_activate(move |app| setup(app, config.clone(), registry.clone(), canvas.clone()));
     application.run();
 }
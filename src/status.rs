@@ -1,18 +1,35 @@
-use std::{process::Command, sync::LazyLock};
+mod audio;
+mod battery;
+mod bluetooth;
+mod cpu;
+mod dbus;
+mod disk;
+mod file;
+mod layout;
+mod memory;
+mod network;
+mod ramp;
+mod temperature;
 
-use regex_lite::Regex;
+use std::{
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
 
-const COLOR_URGENT: Rgba = rgba(0xcf4955ff);
-const COLOR_WARN: Rgba = rgba(0xfbc011ff);
-const COLOR_OK: Rgba = rgba(0x0a8c6cff);
-const COLOR_BG: Rgba = rgba(0x161616ff);
-const COLOR_MUTE: Rgba = rgba(0x777777ff);
-const COLOR_NORMAL: Rgba = rgba(0x256ccfff);
+use serde::{de::DeserializeOwned, Deserialize};
 
-type Rgba = [f64; 4];
-type Bar = (f64, Rgba);
+use crate::config::{Colors, Config, Segment};
 
-const fn rgba(color: u32) -> Rgba {
+pub type Rgba = [f64; 4];
+pub type Bar = (f64, Rgba);
+
+/// A bar drawn over an indicator's main one, starting at
+/// an offset given as a percent of the segment height.
+pub type Overlay = (f64, Bar);
+
+pub const fn rgba(color: u32) -> Rgba {
     let r = ((color >> 24) & 0xFF) as f64 / 255.0;
     let g = ((color >> 16) & 0xFF) as f64 / 255.0;
     let b = ((color >> 8) & 0xFF) as f64 / 255.0;
@@ -20,12 +37,277 @@ const fn rgba(color: u32) -> Rgba {
     [r, g, b, a]
 }
 
+/// Requests a redraw from any thread, for indicators
+/// which are updated by events rather than by polling.
+pub type Redraw = Arc<dyn Fn() + Send + Sync>;
+
+/// A source of status information, drawn as a single segment.
+pub trait Indicator {
+    /// Name of the indicator, as used in the config.
+    fn name(&self) -> &'static str;
+
+    /// Refresh the indicator's state.
+    fn poll(&mut self) -> Result<(), String>;
+
+    /// The bar representing the current state,
+    /// if there is anything to draw.
+    fn value(&self) -> Option<Bar>;
+
+    /// Extra bars to draw over the main one, e.g. markers.
+    fn overlays(&self) -> Vec<Overlay> {
+        vec![]
+    }
+
+    /// A description of the current state, shown on hover.
+    fn details(&self) -> Option<String> {
+        None
+    }
+}
+
+/// How long to wait before watching again after a failure.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Reports a new state (or a failure to get it) from a watching thread.
+pub type Update<'a, T> = &'a mut dyn FnMut(Result<T, String>);
+
+/// State kept up to date by a background thread,
+/// requesting a redraw whenever it changes.
+pub struct Watch<T> {
+    state: Arc<Mutex<Watched<T>>>,
+}
+
+struct Watched<T> {
+    value: Option<T>,
+    error: Option<String>,
+}
+
+impl<T: Clone + Send + 'static> Watch<T> {
+    /// Run `subscribe` in a new thread. It's expected to report the
+    /// initial state and then every change, until it fails (e.g. when
+    /// the connection is lost), after which it's retried.
+    pub fn spawn_retrying(
+        redraw: Redraw,
+        mut subscribe: impl FnMut(Update<T>) -> Result<(), String> + Send + 'static,
+    ) -> Self {
+        Self::spawn(redraw, move |update| loop {
+            if let Err(err) = subscribe(update) {
+                update(Err(err));
+            }
+            thread::sleep(RETRY_DELAY);
+        })
+    }
+
+    fn spawn(redraw: Redraw, watch: impl FnOnce(Update<T>) + Send + 'static) -> Self {
+        let state = Arc::new(Mutex::new(Watched {
+            value: None,
+            error: None,
+        }));
+        let watched = state.clone();
+        thread::spawn(move || {
+            watch(&mut |result| {
+                let mut state = watched.lock().expect("Lock should not be poisoned");
+                match result {
+                    Ok(value) => {
+                        state.value = Some(value);
+                        state.error = None;
+                    }
+                    Err(err) => state.error = Some(err),
+                }
+                drop(state);
+                redraw();
+            })
+        });
+        Watch { state }
+    }
+
+    /// The last known state.
+    pub fn get(&self) -> Option<T> {
+        self.lock().value.clone()
+    }
+
+    /// The last error, if the state couldn't be updated.
+    pub fn error(&self) -> Option<String> {
+        self.lock().error.clone()
+    }
+
+    /// Report the last error, for use in [`Indicator::poll`].
+    pub fn check(&self) -> Result<(), String> {
+        self.error().map_or(Ok(()), Err)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Watched<T>> {
+        self.state.lock().expect("Lock should not be poisoned")
+    }
+}
+
+/// Create the indicator for a segment.
+fn indicator(
+    segment: &Segment,
+    colors: Colors,
+    redraw: &Redraw,
+) -> Result<Box<dyn Indicator>, String> {
+    let indicator: Box<dyn Indicator> = match segment.indicator.as_str() {
+        "battery" => Box::new(battery::Battery::new(
+            colors,
+            options(segment)?,
+            redraw.clone(),
+        )?),
+        "volume" => {
+            let NoOptions {} = options(segment)?;
+            Box::new(audio::Volume::new(colors, redraw.clone()))
+        }
+        "mic" => {
+            let NoOptions {} = options(segment)?;
+            Box::new(audio::Mic::new(colors, redraw.clone()))
+        }
+        "bluetooth" => Box::new(bluetooth::Bluetooth::new(
+            colors,
+            options(segment)?,
+            redraw.clone(),
+        )),
+        "bluetooth_battery" => Box::new(bluetooth::BluetoothBattery::new(
+            colors,
+            options(segment)?,
+            redraw.clone(),
+        )),
+        "wifi" => Box::new(network::Wifi::new(colors, options(segment)?)),
+        "wifi_signal" => Box::new(network::WifiSignal::new(colors, options(segment)?)),
+        "throughput" => Box::new(network::Throughput::new(colors, options(segment)?)),
+        "cpu" => Box::new(cpu::Cpu::new(colors, options(segment)?)),
+        "disk" => Box::new(disk::Disk::new(colors, options(segment)?)),
+        "file" => Box::new(file::StateFile::new(
+            colors,
+            options(segment)?,
+            redraw.clone(),
+        )?),
+        "memory" => Box::new(memory::Memory::new(colors, options(segment)?)),
+        "temperature" => Box::new(temperature::Temperature::new(colors, options(segment)?)?),
+        "layout" => Box::new(layout::Layout::new(
+            colors,
+            options(segment)?,
+            redraw.clone(),
+        )),
+        name => return Err(format!("Unknown indicator: {}", name)),
+    };
+    Ok(indicator)
+}
+
+/// Parse the indicator-specific options of a segment.
+fn options<T: DeserializeOwned>(segment: &Segment) -> Result<T, String> {
+    segment
+        .options
+        .clone()
+        .try_into()
+        .map_err(|err| format!("Invalid options for {}: {}", segment.indicator, err))
+}
+
+/// The options of indicators which don't take any,
+/// so that e.g. a misspelled `height` isn't ignored.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoOptions {}
+
+/// An indicator placed in the layout.
+struct Entry {
+    column: usize,
+    segment: Segment,
+    indicator: Box<dyn Indicator>,
+    error: Option<String>,
+}
+
+/// All the configured indicators.
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub fn new(config: &Config, redraw: Redraw) -> Result<Self, String> {
+        let mut entries = vec![];
+        for (column, col) in config.columns.iter().enumerate() {
+            for segment in &col.segments {
+                entries.push(Entry {
+                    column,
+                    segment: segment.clone(),
+                    indicator: indicator(segment, config.colors, &redraw)?,
+                    error: None,
+                });
+            }
+        }
+        Ok(Registry { entries })
+    }
+
+    /// Refresh every indicator.
+    /// Errors are only reported once, until the indicator recovers.
+    pub fn poll(&mut self) {
+        for entry in &mut self.entries {
+            match entry.indicator.poll() {
+                Ok(()) => entry.error = None,
+                Err(err) => {
+                    if entry.error.as_ref() != Some(&err) {
+                        eprintln!("{}: {}", entry.indicator.name(), err);
+                    }
+                    entry.error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// The bars to draw, in drawing order.
+    pub fn bars(&self) -> Vec<Placement> {
+        let mut bars = vec![];
+        for entry in &self.entries {
+            let segment = &entry.segment;
+            if let Some(bar) = entry.indicator.value() {
+                bars.push(Placement {
+                    column: entry.column,
+                    y: segment.y,
+                    height: segment.height,
+                    bar,
+                });
+            }
+            for (offset, bar) in entry.indicator.overlays() {
+                bars.push(Placement {
+                    column: entry.column,
+                    y: segment.y + offset * segment.height,
+                    height: segment.height,
+                    bar,
+                });
+            }
+        }
+        bars
+    }
+
+    /// Details of the indicator at a position, with `y`
+    /// as a percent of the window height from the bottom.
+    pub fn details(&self, column: usize, y: f64) -> Option<String> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.column == column)
+            .filter(|entry| y >= entry.segment.y && y <= entry.segment.y + entry.segment.height)
+            .find_map(|entry| entry.indicator.details())
+    }
+}
+
+/// A bar positioned in the window.
+pub struct Placement {
+    pub column: usize,
+
+    /// Bottom of the bar, as a percent of the window height.
+    pub y: f64,
+
+    /// Height of a full bar, as a percent of the window height.
+    pub height: f64,
+
+    pub bar: Bar,
+}
+
 /// Run a shell command and get the output.
 fn cmd(cmd: &str, args: &[&str]) -> Result<String, String> {
     let output = Command::new(cmd)
         .args(args)
         .output()
-        .expect("Failed to execute command");
+        .map_err(|err| format!("Failed to execute {}: {}", cmd, err))?;
 
     if output.status.success() {
         let stdout = String::from_utf8(output.stdout)
@@ -40,102 +322,216 @@ fn cmd(cmd: &str, args: &[&str]) -> Result<String, String> {
     }
 }
 
-/// Get a bar representing the battery state.
-pub fn battery() -> Result<Bar, battery::Error> {
-    let manager = battery::Manager::new()?;
-    let batt = manager
-        .batteries()?
-        .next()
-        .expect("Should be at least one battery")?;
-    let bar = match batt.state() {
-        // "Not Charging" state not yet supported,
-        // reported as "Unknown".
-        // https://github.com/svartalf/rust-battery/pull/100
-        battery::State::Unknown => (1.0, COLOR_OK),
-        battery::State::Full => (1.0, COLOR_OK),
-        battery::State::Charging => {
-            let percent = batt.state_of_charge().value as f64;
-            (percent, COLOR_OK)
-        }
-        battery::State::Discharging => {
-            let percent = batt.state_of_charge().value as f64;
-            let color = if percent <= 0.1 {
-                COLOR_URGENT
-            } else {
-                COLOR_WARN
-            };
-            (percent, color)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An indicator with a fixed state.
+    #[derive(Default)]
+    struct Fake {
+        value: Option<Bar>,
+        overlays: Vec<Overlay>,
+        details: Option<&'static str>,
+    }
+
+    impl Indicator for Fake {
+        fn name(&self) -> &'static str {
+            "fake"
         }
-        _ => (1.0, COLOR_BG),
-    };
-    Ok(bar)
-}
 
-/// Get a bar representing the volume state.
-pub fn volume() -> Result<Bar, String> {
-    static PERCENT_RE: LazyLock<Regex> =
-        LazyLock::new(|| Regex::new(r#"(\d{1,3})%"#).expect("Should be a valid regex"));
-
-    let out = cmd("pactl", &["--", "get-sink-mute", "@DEFAULT_SINK@"])?;
-    let muted = out.contains("yes");
-    let fill_color = if muted { COLOR_MUTE } else { COLOR_NORMAL };
-
-    let out = cmd("pactl", &["--", "get-sink-volume", "@DEFAULT_SINK@"])?;
-    let caps = PERCENT_RE.captures(&out).expect("Volume should be present");
-    let volume: f64 = caps
-        .get(1)
-        .expect("Volume should be present")
-        .as_str()
-        .parse()
-        .expect("Volume should be valid number");
-    Ok((volume / 100., fill_color))
-}
+        fn poll(&mut self) -> Result<(), String> {
+            Ok(())
+        }
 
-/// Get a color representing the bluetooth state.
-pub fn bluetooth() -> Result<Rgba, String> {
-    let out = cmd("bluetoothctl", &["show"])?;
-    let color = if out.contains("Powered: yes") {
-        COLOR_NORMAL
-    } else {
-        COLOR_BG
-    };
-    Ok(color)
-}
+        fn value(&self) -> Option<Bar> {
+            self.value
+        }
 
-/// Get a color representing the microphone state.
-pub fn mic() -> Result<Rgba, String> {
-    let out = cmd("pactl", &["--", "get-source-mute", "@DEFAULT_SOURCE@"])?;
-    let muted = out.contains("yes");
-    let color = if muted { COLOR_BG } else { COLOR_URGENT };
-    Ok(color)
-}
+        fn overlays(&self) -> Vec<Overlay> {
+            self.overlays.clone()
+        }
 
-/// Get a color representing the wifi/vpn state.
-pub fn wifi() -> Result<Rgba, String> {
-    let out = cmd("ip", &["address"])?;
-    let color = if !out.contains("state UP") {
-        COLOR_BG
-    } else {
-        let out = cmd("mullvad", &["status"])?;
-        let ssid = cmd("iwgetid", &["-r"]).unwrap_or("".into());
-        if out.contains("Connected") {
-            COLOR_OK
-        } else if ssid.is_empty() {
-            COLOR_MUTE
-        } else {
-            COLOR_URGENT
+        fn details(&self) -> Option<String> {
+            self.details.map(String::from)
         }
-    };
-    Ok(color)
-}
+    }
 
-/// Get a color representing if the current layout is monocle (fake fullscreen).
-pub fn layout() -> Result<Rgba, String> {
-    let out = cmd("cat", &["/tmp/ws_fs"])?;
-    let color = if out.contains("on") {
-        COLOR_WARN
-    } else {
-        COLOR_BG
-    };
-    Ok(color)
+    const RED: Rgba = [1., 0., 0., 1.];
+    const BLUE: Rgba = [0., 0., 1., 1.];
+
+    fn segment(options: &str) -> Segment {
+        toml::from_str(&format!("indicator = \"volume\"\n{}", options))
+            .expect("Segment should be valid")
+    }
+
+    #[test]
+    fn rejects_options_for_indicators_without_any() {
+        let NoOptions {} =
+            options(&segment("height = 0.5")).expect("No options should be accepted");
+        let err = options::<NoOptions>(&segment("heigth = 0.5")).unwrap_err();
+        assert!(err.contains("heigth"), "{}", err);
+    }
+
+    /// A registry of fake indicators, each in a column with a `y` and `height`.
+    fn registry(fakes: Vec<(usize, f64, f64, Fake)>) -> Registry {
+        let entries = fakes
+            .into_iter()
+            .map(|(column, y, height, fake)| Entry {
+                column,
+                segment: Segment {
+                    indicator: "fake".into(),
+                    y,
+                    height,
+                    options: toml::Table::new(),
+                },
+                indicator: Box::new(fake),
+                error: None,
+            })
+            .collect();
+        Registry { entries }
+    }
+
+    fn placements(registry: &Registry) -> Vec<(usize, f64, f64, Bar)> {
+        registry
+            .bars()
+            .into_iter()
+            .map(|placement| {
+                (
+                    placement.column,
+                    placement.y,
+                    placement.height,
+                    placement.bar,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn places_bars_in_their_segment() {
+        let registry = registry(vec![
+            (
+                0,
+                0.5,
+                0.25,
+                Fake {
+                    value: Some((0.5, RED)),
+                    ..Fake::default()
+                },
+            ),
+            (1, 0., 1., Fake::default()),
+            (
+                2,
+                0.,
+                1.,
+                Fake {
+                    value: Some((1., BLUE)),
+                    ..Fake::default()
+                },
+            ),
+        ]);
+        assert_eq!(
+            placements(&registry),
+            vec![(0, 0.5, 0.25, (0.5, RED)), (2, 0., 1., (1., BLUE))]
+        );
+    }
+
+    #[test]
+    fn offsets_overlays_within_their_segment() {
+        let registry = registry(vec![(
+            1,
+            0.5,
+            0.4,
+            Fake {
+                value: Some((0.5, RED)),
+                overlays: vec![(0., (0.1, BLUE)), (0.5, (0.2, BLUE))],
+                ..Fake::default()
+            },
+        )]);
+        assert_eq!(
+            placements(&registry),
+            vec![
+                (1, 0.5, 0.4, (0.5, RED)),
+                (1, 0.5, 0.4, (0.1, BLUE)),
+                (1, 0.7, 0.4, (0.2, BLUE)),
+            ]
+        );
+    }
+
+    #[test]
+    fn draws_overlays_without_a_value() {
+        let registry = registry(vec![(
+            0,
+            0.,
+            1.,
+            Fake {
+                overlays: vec![(0.25, (0.5, BLUE))],
+                ..Fake::default()
+            },
+        )]);
+        assert_eq!(placements(&registry), vec![(0, 0.25, 1., (0.5, BLUE))]);
+    }
+
+    #[test]
+    fn finds_details_by_position() {
+        let registry = registry(vec![
+            (
+                0,
+                0.,
+                0.5,
+                Fake {
+                    details: Some("bottom"),
+                    ..Fake::default()
+                },
+            ),
+            (
+                0,
+                0.5,
+                0.5,
+                Fake {
+                    details: Some("top"),
+                    ..Fake::default()
+                },
+            ),
+            (
+                1,
+                0.,
+                1.,
+                Fake {
+                    details: Some("second"),
+                    ..Fake::default()
+                },
+            ),
+        ]);
+        assert_eq!(registry.details(0, 0.25).as_deref(), Some("bottom"));
+        assert_eq!(registry.details(0, 0.75).as_deref(), Some("top"));
+        assert_eq!(registry.details(1, 0.25).as_deref(), Some("second"));
+        assert_eq!(registry.details(2, 0.25), None);
+    }
+
+    #[test]
+    fn prefers_details_of_the_last_overlapping_segment() {
+        let registry = registry(vec![
+            (
+                0,
+                0.,
+                1.,
+                Fake {
+                    details: Some("under"),
+                    ..Fake::default()
+                },
+            ),
+            (
+                0,
+                0.5,
+                0.25,
+                Fake {
+                    details: Some("over"),
+                    ..Fake::default()
+                },
+            ),
+            (0, 0.5, 0.5, Fake::default()),
+        ]);
+        assert_eq!(registry.details(0, 0.6).as_deref(), Some("over"));
+        assert_eq!(registry.details(0, 0.9).as_deref(), Some("under"));
+    }
 }
@@ -0,0 +1,111 @@
+mod pactl;
+#[cfg(feature = "pulse")]
+mod pulse;
+
+use std::fmt;
+
+use super::{Bar, Indicator, Redraw, Update, Watch};
+use crate::config::Colors;
+
+/// Which default device to query.
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    Sink,
+    Source,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Sink => write!(f, "sink"),
+            Kind::Source => write!(f, "source"),
+        }
+    }
+}
+
+/// Volume and mute state of an audio device.
+#[derive(Debug, Clone, Copy)]
+pub struct Device {
+    volume: f64,
+    muted: bool,
+}
+
+/// Subscribe to changes of the default device,
+/// natively if possible and through `pactl` otherwise.
+fn subscribe(kind: Kind, update: Update<Device>) -> Result<(), String> {
+    #[cfg(feature = "pulse")]
+    if let Ok(mut client) = pulse::Client::connect() {
+        return client.subscribe(kind, update);
+    }
+    pactl::subscribe(kind, update)
+}
+
+/// A bar representing the output volume and mute state.
+pub struct Volume {
+    colors: Colors,
+    sink: Watch<Device>,
+}
+
+impl Volume {
+    pub fn new(colors: Colors, redraw: Redraw) -> Self {
+        Volume {
+            colors,
+            sink: Watch::spawn_retrying(redraw, |update| subscribe(Kind::Sink, update)),
+        }
+    }
+}
+
+impl Indicator for Volume {
+    fn name(&self) -> &'static str {
+        "volume"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        self.sink.check()
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let sink = self.sink.get()?;
+        let fill_color = if sink.muted {
+            self.colors.mute
+        } else {
+            self.colors.normal
+        };
+        Some((sink.volume, fill_color))
+    }
+}
+
+/// A color representing the microphone mute state.
+pub struct Mic {
+    colors: Colors,
+    source: Watch<Device>,
+}
+
+impl Mic {
+    pub fn new(colors: Colors, redraw: Redraw) -> Self {
+        Mic {
+            colors,
+            source: Watch::spawn_retrying(redraw, |update| subscribe(Kind::Source, update)),
+        }
+    }
+}
+
+impl Indicator for Mic {
+    fn name(&self) -> &'static str {
+        "mic"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        self.source.check()
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let source = self.source.get()?;
+        let color = if source.muted {
+            self.colors.bg
+        } else {
+            self.colors.urgent
+        };
+        Some((1.0, color))
+    }
+}
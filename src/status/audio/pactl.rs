@@ -0,0 +1,93 @@
+//! Fallback for when the pulse server can't be reached natively.
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::LazyLock,
+};
+
+use regex_lite::Regex;
+
+use super::{Device, Kind};
+use crate::status::cmd;
+use crate::status::Update;
+
+/// Get the state of the default sink or source through `pactl`.
+pub fn device(kind: Kind) -> Result<Device, String> {
+    let (mute_cmd, volume_cmd, name) = match kind {
+        Kind::Sink => ("get-sink-mute", "get-sink-volume", "@DEFAULT_SINK@"),
+        Kind::Source => ("get-source-mute", "get-source-volume", "@DEFAULT_SOURCE@"),
+    };
+
+    let out = cmd("pactl", &["--", mute_cmd, name])?;
+    let muted = out.contains("yes");
+
+    let out = cmd("pactl", &["--", volume_cmd, name])?;
+    Ok(Device {
+        volume: volume(&out)?,
+        muted,
+    })
+}
+
+/// Parse the volume of the first channel out of `pactl get-*-volume`.
+fn volume(out: &str) -> Result<f64, String> {
+    static PERCENT_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(\d{1,3})%"#).expect("Should be a valid regex"));
+
+    let percent: f64 = PERCENT_RE
+        .captures(out)
+        .and_then(|caps| caps[1].parse().ok())
+        .ok_or_else(|| format!("Unexpected pactl output: {}", out))?;
+    Ok(percent / 100.)
+}
+
+/// Watch the default sink or source through `pactl subscribe`,
+/// calling `on_change` with its current state and again whenever
+/// it (or the default device) changes.
+/// Blocks until `pactl` exits.
+pub fn subscribe(kind: Kind, on_change: Update<Device>) -> Result<(), String> {
+    let facility = match kind {
+        Kind::Sink => "on sink #",
+        Kind::Source => "on source #",
+    };
+
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to execute pactl: {}", err))?;
+    let stdout = child.stdout.take().expect("Stdout should be piped");
+
+    on_change(device(kind));
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.contains(facility) || line.contains("on server") {
+            on_change(device(kind));
+        }
+    }
+
+    let status = child.wait().map_err(|err| err.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("pactl subscribe exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_volume() {
+        let out = "Volume: front-left: 42597 /  65% / -11.23 dB,   \
+                   front-right: 42597 /  65% / -11.23 dB\n        balance 0.00";
+        assert_eq!(volume(out), Ok(0.65));
+    }
+
+    #[test]
+    fn rejects_unexpected_output() {
+        assert!(volume("Volume: n/a").is_err());
+    }
+}
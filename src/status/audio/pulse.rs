@@ -0,0 +1,298 @@
+//! Minimal bindings to libpulse, just enough to
+//! read the volume and mute state of the default devices.
+
+use std::{
+    cell::Cell,
+    ffi::{c_char, c_int, c_void},
+    ptr,
+};
+
+use super::{Device, Kind};
+use crate::status::Update;
+
+const PA_CONTEXT_READY: c_int = 4;
+const PA_CONTEXT_FAILED: c_int = 5;
+const PA_CONTEXT_TERMINATED: c_int = 6;
+const PA_OPERATION_RUNNING: c_int = 0;
+const PA_VOLUME_NORM: u32 = 0x10000;
+const PA_SUBSCRIPTION_MASK_SINK: c_int = 0x0001;
+const PA_SUBSCRIPTION_MASK_SOURCE: c_int = 0x0002;
+const PA_SUBSCRIPTION_MASK_SERVER: c_int = 0x0080;
+const PA_CHANNELS_MAX: usize = 32;
+
+#[repr(C)]
+struct SampleSpec {
+    format: c_int,
+    rate: u32,
+    channels: u8,
+}
+
+#[repr(C)]
+struct ChannelMap {
+    channels: u8,
+    map: [c_int; PA_CHANNELS_MAX],
+}
+
+#[repr(C)]
+struct CVolume {
+    channels: u8,
+    values: [u32; PA_CHANNELS_MAX],
+}
+
+/// The leading fields shared by `pa_sink_info` and `pa_source_info`.
+/// Only ever accessed through pointers handed out by libpulse.
+#[repr(C)]
+struct DeviceInfo {
+    name: *const c_char,
+    index: u32,
+    description: *const c_char,
+    sample_spec: SampleSpec,
+    channel_map: ChannelMap,
+    owner_module: u32,
+    volume: CVolume,
+    mute: c_int,
+}
+
+#[repr(C)]
+struct Mainloop {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct MainloopApi {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct Context {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct Operation {
+    _private: [u8; 0],
+}
+
+type InfoCallback = extern "C" fn(*mut Context, *const DeviceInfo, c_int, *mut c_void);
+type SubscribeCallback = extern "C" fn(*mut Context, c_int, u32, *mut c_void);
+type SuccessCallback = extern "C" fn(*mut Context, c_int, *mut c_void);
+
+#[link(name = "pulse")]
+extern "C" {
+    fn pa_mainloop_new() -> *mut Mainloop;
+    fn pa_mainloop_free(m: *mut Mainloop);
+    fn pa_mainloop_get_api(m: *mut Mainloop) -> *mut MainloopApi;
+    fn pa_mainloop_iterate(m: *mut Mainloop, block: c_int, retval: *mut c_int) -> c_int;
+
+    fn pa_context_new(api: *mut MainloopApi, name: *const c_char) -> *mut Context;
+    fn pa_context_unref(c: *mut Context);
+    fn pa_context_connect(
+        c: *mut Context,
+        server: *const c_char,
+        flags: c_int,
+        api: *const c_void,
+    ) -> c_int;
+    fn pa_context_disconnect(c: *mut Context);
+    fn pa_context_get_state(c: *mut Context) -> c_int;
+    fn pa_context_get_sink_info_by_name(
+        c: *mut Context,
+        name: *const c_char,
+        cb: InfoCallback,
+        userdata: *mut c_void,
+    ) -> *mut Operation;
+    fn pa_context_get_source_info_by_name(
+        c: *mut Context,
+        name: *const c_char,
+        cb: InfoCallback,
+        userdata: *mut c_void,
+    ) -> *mut Operation;
+
+    fn pa_context_set_subscribe_callback(
+        c: *mut Context,
+        cb: Option<SubscribeCallback>,
+        userdata: *mut c_void,
+    );
+    fn pa_context_subscribe(
+        c: *mut Context,
+        mask: c_int,
+        cb: Option<SuccessCallback>,
+        userdata: *mut c_void,
+    ) -> *mut Operation;
+
+    fn pa_operation_get_state(o: *mut Operation) -> c_int;
+    fn pa_operation_unref(o: *mut Operation);
+
+    fn pa_cvolume_avg(v: *const CVolume) -> u32;
+}
+
+/// A connection to the PulseAudio (or pipewire-pulse) server.
+pub struct Client {
+    mainloop: *mut Mainloop,
+    context: *mut Context,
+}
+
+impl Client {
+    /// Connect to the default server, blocking until it's ready.
+    pub fn connect() -> Result<Self, String> {
+        // SAFETY: The mainloop and context are owned by the client
+        // and released in `Drop`, including on the error paths below.
+        unsafe {
+            let mainloop = pa_mainloop_new();
+            if mainloop.is_null() {
+                return Err("Failed to create the pulse mainloop".into());
+            }
+            let context = pa_context_new(pa_mainloop_get_api(mainloop), c"sema".as_ptr());
+            let client = Client { mainloop, context };
+            if context.is_null() {
+                return Err("Failed to create the pulse context".into());
+            }
+            if pa_context_connect(context, ptr::null(), 0, ptr::null()) < 0 {
+                return Err("Failed to connect to the pulse server".into());
+            }
+            loop {
+                match pa_context_get_state(context) {
+                    PA_CONTEXT_READY => break,
+                    PA_CONTEXT_FAILED | PA_CONTEXT_TERMINATED => {
+                        return Err("Failed to connect to the pulse server".into());
+                    }
+                    _ => client.iterate()?,
+                }
+            }
+            Ok(client)
+        }
+    }
+
+    /// Get the state of the default sink or source.
+    pub fn device(&mut self, kind: Kind) -> Result<Device, String> {
+        let mut device: Option<Device> = None;
+        let userdata = &mut device as *mut Option<Device> as *mut c_void;
+
+        // SAFETY: `device` outlives the operation,
+        // as we block until it has completed.
+        unsafe {
+            if pa_context_get_state(self.context) != PA_CONTEXT_READY {
+                return Err("Lost connection to the pulse server".into());
+            }
+            let op = match kind {
+                Kind::Sink => pa_context_get_sink_info_by_name(
+                    self.context,
+                    c"@DEFAULT_SINK@".as_ptr(),
+                    on_info,
+                    userdata,
+                ),
+                Kind::Source => pa_context_get_source_info_by_name(
+                    self.context,
+                    c"@DEFAULT_SOURCE@".as_ptr(),
+                    on_info,
+                    userdata,
+                ),
+            };
+            if op.is_null() {
+                return Err("Failed to query the pulse server".into());
+            }
+            let result = loop {
+                if pa_operation_get_state(op) != PA_OPERATION_RUNNING {
+                    break Ok(());
+                }
+                if let Err(err) = self.iterate() {
+                    break Err(err);
+                }
+            };
+            pa_operation_unref(op);
+            result?;
+        }
+        device.ok_or_else(|| format!("No default {} found", kind))
+    }
+
+    /// Watch the default sink or source, calling `on_change` with its
+    /// current state and again whenever it (or the default device) changes.
+    /// Blocks until the connection to the server is lost.
+    pub fn subscribe(&mut self, kind: Kind, on_change: Update<Device>) -> Result<(), String> {
+        let changed = Cell::new(false);
+        let mask = match kind {
+            Kind::Sink => PA_SUBSCRIPTION_MASK_SINK,
+            Kind::Source => PA_SUBSCRIPTION_MASK_SOURCE,
+        } | PA_SUBSCRIPTION_MASK_SERVER;
+
+        // SAFETY: `changed` outlives the subscription,
+        // as the callback is unset before returning.
+        unsafe {
+            pa_context_set_subscribe_callback(
+                self.context,
+                Some(on_event),
+                &changed as *const Cell<bool> as *mut c_void,
+            );
+            let op = pa_context_subscribe(self.context, mask, None, ptr::null_mut());
+            if !op.is_null() {
+                pa_operation_unref(op);
+            }
+        }
+
+        on_change(self.device(kind));
+        let result = loop {
+            if let Err(err) = self.iterate() {
+                break Err(err);
+            }
+            // SAFETY: The context is valid for the lifetime of the client.
+            if unsafe { pa_context_get_state(self.context) } != PA_CONTEXT_READY {
+                break Err("Lost connection to the pulse server".into());
+            }
+            if changed.replace(false) {
+                on_change(self.device(kind));
+            }
+        };
+
+        // SAFETY: See above.
+        unsafe {
+            pa_context_set_subscribe_callback(self.context, None, ptr::null_mut());
+        }
+        result
+    }
+
+    /// Run a single iteration of the mainloop, waiting for events.
+    fn iterate(&self) -> Result<(), String> {
+        // SAFETY: The mainloop is valid for the lifetime of the client.
+        let ret = unsafe { pa_mainloop_iterate(self.mainloop, 1, ptr::null_mut()) };
+        if ret < 0 {
+            Err("Failed to run the pulse mainloop".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // SAFETY: Both pointers are owned by the client
+        // and not used again.
+        unsafe {
+            if !self.context.is_null() {
+                pa_context_disconnect(self.context);
+                pa_context_unref(self.context);
+            }
+            pa_mainloop_free(self.mainloop);
+        }
+    }
+}
+
+extern "C" fn on_info(_: *mut Context, info: *const DeviceInfo, eol: c_int, userdata: *mut c_void) {
+    if eol != 0 || info.is_null() {
+        return;
+    }
+    // SAFETY: `userdata` is the `Option<Device>` passed in `Client::device`,
+    // and `info` is valid for the duration of the callback.
+    unsafe {
+        let device = &mut *(userdata as *mut Option<Device>);
+        let volume = pa_cvolume_avg(&(*info).volume);
+        *device = Some(Device {
+            volume: volume as f64 / PA_VOLUME_NORM as f64,
+            muted: (*info).mute != 0,
+        });
+    }
+}
+
+extern "C" fn on_event(_: *mut Context, _: c_int, _: u32, userdata: *mut c_void) {
+    // SAFETY: `userdata` is the `Cell<bool>` passed in `Client::subscribe`.
+    let changed = unsafe { &*(userdata as *const Cell<bool>) };
+    changed.set(true);
+}
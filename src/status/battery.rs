@@ -0,0 +1,531 @@
+mod actions;
+mod sysfs;
+mod upower;
+
+use std::{fmt, path::Path, time::Duration};
+
+use actions::Actions;
+use battery::{Manager, State};
+use serde::Deserialize;
+use zbus::blocking::Connection;
+
+use super::{
+    ramp::{Direction, Ramp, Stop},
+    Bar, Indicator, Overlay, Redraw, Watch,
+};
+use crate::config::Colors;
+
+/// How far outside of its thresholds a battery is still considered
+/// held by them, as they're only approximately respected.
+const THRESHOLD_MARGIN: f64 = 0.03;
+
+/// Where to get the battery state from.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Read every battery from sysfs, on every refresh.
+    Sysfs,
+
+    /// UPower's display device, updated as soon as it changes.
+    Upower,
+}
+
+/// What to show on machines without a battery.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoBattery {
+    /// Leave the segment empty.
+    Hide,
+
+    /// A full bar, to show we're on AC power.
+    Ac,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    backend: Backend,
+    no_battery: NoBattery,
+
+    /// Colors while discharging, used at or below each level.
+    /// Above all of them the bar is `warn`.
+    levels: Vec<Stop>,
+
+    /// Blend the colors between levels rather than stepping.
+    /// Above all of them the bar is still `warn`, so this
+    /// only makes a difference with several levels.
+    interpolate: bool,
+
+    /// Scale the bar against the design capacity, showing
+    /// the capacity lost to wear as a dim cap.
+    wear: bool,
+
+    /// Levels at which to send a notification while discharging.
+    notify: Vec<f64>,
+
+    /// Level at which to run `critical_command` while discharging.
+    critical: Option<f64>,
+    critical_command: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            backend: Backend::Sysfs,
+            no_battery: NoBattery::Hide,
+            levels: vec![Stop::new(0.1, "urgent")],
+            interpolate: false,
+            wear: false,
+            notify: vec![],
+            critical: None,
+            critical_command: vec![],
+        }
+    }
+}
+
+/// Whether the batteries are charging.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Charging,
+    Discharging,
+    Full,
+
+    /// Plugged in but held below full, e.g. by a charge threshold.
+    NotCharging,
+    Empty,
+    Unknown,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Charging => write!(f, "charging"),
+            Status::Discharging => write!(f, "discharging"),
+            Status::Full => write!(f, "full"),
+            Status::NotCharging => write!(f, "not charging"),
+            Status::Empty => write!(f, "empty"),
+            Status::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl From<State> for Status {
+    fn from(state: State) -> Self {
+        match state {
+            State::Charging => Status::Charging,
+            State::Discharging => Status::Discharging,
+            State::Full => Status::Full,
+            State::Empty => Status::Empty,
+            _ => Status::Unknown,
+        }
+    }
+}
+
+/// The combined state of all batteries.
+#[derive(Debug, Clone)]
+pub struct Charge {
+    /// Energy-weighted charge, from 0 to 1.
+    percent: f64,
+    status: Status,
+
+    /// Estimates, only known with UPower.
+    time_to_empty: Option<Duration>,
+    time_to_full: Option<Duration>,
+
+    /// Full capacity as a percent of the design capacity.
+    health: Option<f64>,
+
+    /// Charge cycles of the most used battery.
+    cycles: Option<u32>,
+}
+
+/// Where the battery state is read from.
+enum Source {
+    /// Polled from sysfs on every refresh.
+    Sysfs {
+        manager: Option<Manager>,
+        charge: Option<Charge>,
+    },
+
+    /// Kept up to date by UPower's signals.
+    Upower(Watch<Option<Charge>>),
+}
+
+/// A bar representing the battery charge and state.
+pub struct Battery {
+    colors: Colors,
+    no_battery: NoBattery,
+    levels: Ramp,
+    wear: bool,
+    actions: Actions,
+    source: Source,
+}
+
+impl Battery {
+    pub fn new(colors: Colors, options: Options, redraw: Redraw) -> Result<Self, String> {
+        let levels = Ramp::parse(
+            &options.levels,
+            Direction::Falling,
+            options.interpolate,
+            &colors,
+        )?;
+        let source = match options.backend {
+            Backend::Sysfs => Source::Sysfs {
+                manager: None,
+                charge: None,
+            },
+            Backend::Upower => Source::Upower(Watch::spawn_retrying(redraw, |update| {
+                Connection::system()
+                    .and_then(|conn| upower::subscribe(&conn, update))
+                    .map_err(|err| err.to_string())
+            })),
+        };
+        Ok(Battery {
+            colors,
+            no_battery: options.no_battery,
+            levels,
+            wear: options.wear,
+            actions: Actions::new(&options.notify, options.critical, options.critical_command),
+            source,
+        })
+    }
+
+    /// How much of the design capacity is left, if the bar is scaled to it.
+    fn health(&self, charge: &Charge) -> Option<f64> {
+        charge
+            .health
+            .filter(|_| self.wear)
+            .map(|health| health.min(1.))
+    }
+
+    /// The current state, or `None` if there's no battery.
+    fn charge(&self) -> Option<Option<Charge>> {
+        match &self.source {
+            Source::Sysfs { charge, .. } => Some(charge.clone()),
+            Source::Upower(watch) => watch.get(),
+        }
+    }
+}
+
+impl Indicator for Battery {
+    fn name(&self) -> &'static str {
+        "battery"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        match &mut self.source {
+            Source::Sysfs { manager, charge } => {
+                let manager = match manager {
+                    Some(manager) => manager,
+                    None => {
+                        manager.insert(Manager::new().map_err(|_| "Failed to get battery info")?)
+                    }
+                };
+                *charge = query(manager)?;
+            }
+            Source::Upower(watch) => watch.check()?,
+        }
+        match self.charge() {
+            Some(Some(charge)) => self.actions.check(&charge),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let Some(charge) = self.charge()? else {
+            return match self.no_battery {
+                NoBattery::Hide => None,
+                NoBattery::Ac => Some((1.0, self.colors.mute)),
+            };
+        };
+        let bar = match charge.status {
+            Status::Unknown => (1.0, self.colors.ok),
+            Status::Full => (1.0, self.colors.ok),
+            Status::Charging => (charge.percent, self.colors.ok),
+            Status::NotCharging => (charge.percent, self.colors.normal),
+            Status::Discharging => {
+                let color = self.levels.color(charge.percent, self.colors.warn);
+                (charge.percent, color)
+            }
+            Status::Empty => (1.0, self.colors.bg),
+        };
+        match self.health(&charge) {
+            Some(health) => Some((bar.0 * health, bar.1)),
+            None => Some(bar),
+        }
+    }
+
+    fn overlays(&self) -> Vec<Overlay> {
+        let Some(Some(charge)) = self.charge() else {
+            return vec![];
+        };
+        match self.health(&charge) {
+            Some(health) if health < 1. => {
+                let [r, g, b, a] = self.colors.mute;
+                vec![(health, (1. - health, [r, g, b, a * 0.5]))]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn details(&self) -> Option<String> {
+        let Some(charge) = self.charge()? else {
+            return Some("No battery".into());
+        };
+        let mut details = format!("Battery {:.0}%, {}", charge.percent * 100., charge.status);
+        let remaining = match charge.status {
+            Status::Discharging => charge.time_to_empty.map(|time| (time, "left")),
+            Status::Charging => charge.time_to_full.map(|time| (time, "until full")),
+            _ => None,
+        };
+        if let Some((time, until)) = remaining {
+            let minutes = time.as_secs() / 60;
+            details += &format!(" ({}h{:02} {})", minutes / 60, minutes % 60, until);
+        }
+        if let Some(health) = charge.health {
+            details += &format!("\nHealth {:.0}%", health * 100.);
+        }
+        if let Some(cycles) = charge.cycles {
+            details += &format!("\n{} cycles", cycles);
+        }
+        Some(details)
+    }
+}
+
+/// What a single battery reports.
+#[derive(Debug, Clone)]
+struct Reading {
+    /// Energy now, when full and when new, in the same unit for all batteries.
+    energy: f64,
+    full: f64,
+    design: f64,
+
+    /// Charge from 0 to 1, for when the energy isn't known.
+    percent: f64,
+    status: Status,
+    cycles: Option<u32>,
+}
+
+/// Read all batteries and combine them into one.
+fn query(manager: &Manager) -> Result<Option<Charge>, String> {
+    let batteries = manager
+        .batteries()
+        .map_err(|_| "Failed to get battery info")?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Failed to get battery info")?;
+    let readings: Vec<_> = batteries
+        .iter()
+        .map(|battery| Reading {
+            energy: battery.energy().value as f64,
+            full: battery.energy_full().value as f64,
+            design: battery.energy_full_design().value as f64,
+            percent: battery.state_of_charge().value as f64,
+            status: battery.state().into(),
+            cycles: battery.cycle_count(),
+        })
+        .collect();
+    // The `battery` crate reports "Not charging" as unknown, so
+    // prefer sysfs, keeping the crate's state as a fallback.
+    // https://github.com/svartalf/rust-battery/pull/100
+    let supplies = sysfs::batteries(Path::new(sysfs::SYSFS))?;
+    Ok(combine(&readings, &supplies))
+}
+
+/// Combine all batteries into one, or `None` if there aren't any.
+/// The status of each comes from `supplies` if sysfs lists any.
+fn combine(batteries: &[Reading], supplies: &[sysfs::Supply]) -> Option<Charge> {
+    if batteries.is_empty() {
+        return None;
+    }
+
+    let energy: f64 = batteries.iter().map(|b| b.energy).sum();
+    let full: f64 = batteries.iter().map(|b| b.full).sum();
+    let design: f64 = batteries.iter().map(|b| b.design).sum();
+    let health = (design > 0.).then(|| full / design);
+    let cycles = batteries.iter().filter_map(|b| b.cycles).max();
+    let percent = if full > 0. {
+        energy / full
+    } else {
+        let total: f64 = batteries.iter().map(|b| b.percent).sum();
+        total / batteries.len() as f64
+    };
+
+    let statuses: Vec<Status> = if supplies.is_empty() {
+        batteries.iter().map(|b| b.status).collect()
+    } else {
+        supplies.iter().map(|supply| supply.status).collect()
+    };
+
+    // Charging or discharging if any battery is, e.g. while
+    // one is drained before the other. Full only if all are.
+    let mut status = if statuses.contains(&Status::Discharging) {
+        Status::Discharging
+    } else if statuses.contains(&Status::Charging) {
+        Status::Charging
+    } else if statuses.iter().all(|status| *status == Status::Full) {
+        Status::Full
+    } else if statuses.contains(&Status::NotCharging) {
+        Status::NotCharging
+    } else {
+        statuses[0]
+    };
+
+    // Some firmware reports a battery held by its thresholds as unknown.
+    let start_threshold = supplies
+        .iter()
+        .filter_map(|supply| supply.start_threshold)
+        .reduce(f64::max)
+        .unwrap_or(0.);
+    let end_threshold = supplies
+        .iter()
+        .filter_map(|supply| supply.end_threshold)
+        .reduce(f64::min);
+    let held = end_threshold.is_some_and(|end| {
+        end < 1.
+            && percent >= start_threshold - THRESHOLD_MARGIN
+            && percent <= end + THRESHOLD_MARGIN
+    });
+    if status == Status::Unknown && held {
+        status = Status::NotCharging;
+    }
+
+    Some(Charge {
+        percent,
+        status,
+        time_to_empty: None,
+        time_to_full: None,
+        health,
+        cycles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(energy: f64, full: f64, design: f64, status: Status) -> Reading {
+        Reading {
+            energy,
+            full,
+            design,
+            percent: if full > 0. { energy / full } else { 0. },
+            status,
+            cycles: None,
+        }
+    }
+
+    fn supply(status: Status) -> sysfs::Supply {
+        sysfs::Supply {
+            status,
+            start_threshold: None,
+            end_threshold: None,
+        }
+    }
+
+    #[test]
+    fn no_battery() {
+        assert!(combine(&[], &[supply(Status::Full)]).is_none());
+    }
+
+    #[test]
+    fn weighs_charge_by_energy() {
+        let batteries = [
+            Reading {
+                cycles: Some(300),
+                ..reading(10., 20., 25., Status::Discharging)
+            },
+            Reading {
+                cycles: Some(40),
+                ..reading(60., 80., 80., Status::Unknown)
+            },
+        ];
+        let charge = combine(&batteries, &[]).unwrap();
+        assert_eq!(charge.percent, 0.7);
+        assert_eq!(charge.status, Status::Discharging);
+        assert_eq!(charge.health, Some(100. / 105.));
+        assert_eq!(charge.cycles, Some(300));
+    }
+
+    #[test]
+    fn averages_charge_without_energy() {
+        let batteries = [
+            Reading {
+                percent: 0.2,
+                ..reading(0., 0., 0., Status::Charging)
+            },
+            Reading {
+                percent: 0.6,
+                ..reading(0., 0., 0., Status::Full)
+            },
+        ];
+        let charge = combine(&batteries, &[]).unwrap();
+        assert_eq!(charge.percent, 0.4);
+        assert_eq!(charge.status, Status::Charging);
+        assert_eq!(charge.health, None);
+    }
+
+    #[test]
+    fn combines_statuses() {
+        let status = |statuses: &[Status]| {
+            let batteries = [reading(5., 10., 10., Status::Unknown)];
+            let supplies: Vec<_> = statuses.iter().copied().map(supply).collect();
+            combine(&batteries, &supplies).unwrap().status
+        };
+        use Status::*;
+        assert_eq!(status(&[Charging, Discharging]), Discharging);
+        assert_eq!(status(&[Full, Charging]), Charging);
+        assert_eq!(status(&[Full, Full]), Full);
+        assert_eq!(status(&[Full, NotCharging]), NotCharging);
+        assert_eq!(status(&[Unknown, Full]), Unknown);
+        // Falls back to the crate's state without sysfs.
+        assert_eq!(status(&[]), Unknown);
+    }
+
+    /// The status of a battery at a charge, given its sysfs files.
+    fn held_status(percent: f64, files: &[(&str, &str)]) -> Status {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("class/power_supply/BAT0");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("type"), "Battery\n").unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), format!("{}\n", contents)).unwrap();
+        }
+        let supplies = sysfs::batteries(root.path()).unwrap();
+        let batteries = [reading(percent * 50., 50., 50., Status::Unknown)];
+        combine(&batteries, &supplies).unwrap().status
+    }
+
+    #[test]
+    fn reports_not_charging() {
+        assert_eq!(
+            held_status(0.6, &[("status", "Not charging")]),
+            Status::NotCharging
+        );
+    }
+
+    #[test]
+    fn unknown_within_thresholds_is_held() {
+        let thresholds = [
+            ("status", "Unknown"),
+            ("charge_start_threshold", "75"),
+            ("charge_stop_threshold", "80"),
+        ];
+        assert_eq!(held_status(0.78, &thresholds), Status::NotCharging);
+        // Thresholds are only approximately respected.
+        assert_eq!(held_status(0.82, &thresholds), Status::NotCharging);
+        assert_eq!(held_status(0.73, &thresholds), Status::NotCharging);
+    }
+
+    #[test]
+    fn unknown_outside_thresholds_stays_unknown() {
+        let thresholds = [
+            ("status", "Unknown"),
+            ("charge_control_start_threshold", "75"),
+            ("charge_control_end_threshold", "80"),
+        ];
+        assert_eq!(held_status(0.5, &thresholds), Status::Unknown);
+        assert_eq!(held_status(0.9, &thresholds), Status::Unknown);
+        // Without thresholds, there's nothing to be held by.
+        assert_eq!(held_status(0.8, &[("status", "Unknown")]), Status::Unknown);
+    }
+}
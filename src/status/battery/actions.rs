@@ -0,0 +1,278 @@
+//! Notifications and commands fired as the battery drains.
+
+use std::{collections::HashMap, process::Command, thread};
+
+use zbus::{blocking::Connection, zvariant::Value};
+
+use super::{Charge, Status};
+
+/// How far above a level the charge has to rise again before
+/// its action can fire again, so it isn't repeated as the charge
+/// hovers around the level.
+const HYSTERESIS: f64 = 0.02;
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+
+/// A level and whether its action fired during the current discharge.
+struct Trigger {
+    level: f64,
+    fired: bool,
+}
+
+impl Trigger {
+    fn new(level: f64) -> Self {
+        Trigger {
+            level,
+            fired: false,
+        }
+    }
+
+    /// Whether the action should fire now. Only the
+    /// first time the level is reached while discharging.
+    fn check(&mut self, charge: &Charge) -> bool {
+        let discharging = charge.status == Status::Discharging;
+        if !discharging || charge.percent > self.level + HYSTERESIS {
+            self.fired = false;
+        } else if charge.percent <= self.level && !self.fired {
+            self.fired = true;
+            return true;
+        }
+        false
+    }
+}
+
+pub struct Actions {
+    notify: Vec<Trigger>,
+    critical: Option<Trigger>,
+    critical_command: Vec<String>,
+
+    /// The last notification sent, replaced by the next one.
+    notification: u32,
+}
+
+impl Actions {
+    pub fn new(notify: &[f64], critical: Option<f64>, critical_command: Vec<String>) -> Self {
+        Actions {
+            notify: notify.iter().map(|level| Trigger::new(*level)).collect(),
+            critical: critical.map(Trigger::new),
+            critical_command,
+            notification: 0,
+        }
+    }
+
+    /// Whether the charge just dropped to a level to notify at,
+    /// and to the critical level.
+    fn reached(&mut self, charge: &Charge) -> (bool, bool) {
+        // When several levels are reached at once, a single notification is enough.
+        let mut notify = false;
+        for trigger in &mut self.notify {
+            notify |= trigger.check(charge);
+        }
+        let critical = self
+            .critical
+            .as_mut()
+            .is_some_and(|trigger| trigger.check(charge));
+        (notify, critical)
+    }
+
+    /// Fire the actions of the levels the charge just dropped to.
+    pub fn check(&mut self, charge: &Charge) -> Result<(), String> {
+        let (notify, critical) = self.reached(charge);
+        if critical {
+            if let Some((program, args)) = self.critical_command.split_first() {
+                let mut command = Command::new(program);
+                command.args(args);
+                thread::spawn(move || match command.status() {
+                    Ok(status) if !status.success() => {
+                        eprintln!("battery: critical command exited with {}", status)
+                    }
+                    Err(err) => eprintln!("battery: failed to run critical command: {}", err),
+                    Ok(_) => {}
+                });
+            }
+        }
+        if notify || critical {
+            self.notification = Connection::session()
+                .and_then(|conn| self.send(&conn, charge, critical))
+                .map_err(|err| format!("Failed to send notification: {}", err))?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, conn: &Connection, charge: &Charge, critical: bool) -> zbus::Result<u32> {
+        let mut body = format!("{:.0}% left", charge.percent * 100.);
+        if let Some(time) = charge.time_to_empty {
+            let minutes = time.as_secs() / 60;
+            body += &format!(", about {}h{:02}", minutes / 60, minutes % 60);
+        }
+        let summary = if critical {
+            "Battery critical"
+        } else {
+            "Battery low"
+        };
+        let urgency: u8 = if critical { 2 } else { 1 };
+        let hints = HashMap::from([("urgency", Value::from(urgency))]);
+
+        let reply = conn.call_method(
+            Some(NOTIFICATIONS),
+            "/org/freedesktop/Notifications",
+            Some(NOTIFICATIONS),
+            "Notify",
+            &(
+                "sema",
+                self.notification,
+                "battery-caution",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1,
+            ),
+        )?;
+        reply.body().deserialize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use zbus::{blocking::connection, interface, zvariant::OwnedValue};
+
+    use super::*;
+    use crate::status::dbus::PrivateBus;
+
+    fn charge(percent: f64, status: Status) -> Charge {
+        Charge {
+            percent,
+            status,
+            time_to_empty: None,
+            time_to_full: None,
+            health: None,
+            cycles: None,
+        }
+    }
+
+    #[test]
+    fn fires_once_at_level() {
+        let mut trigger = Trigger::new(0.2);
+        assert!(!trigger.check(&charge(0.25, Status::Discharging)));
+        assert!(trigger.check(&charge(0.2, Status::Discharging)));
+        assert!(!trigger.check(&charge(0.19, Status::Discharging)));
+        assert!(!trigger.check(&charge(0.1, Status::Discharging)));
+    }
+
+    #[test]
+    fn rearms_when_charging() {
+        let mut trigger = Trigger::new(0.2);
+        assert!(trigger.check(&charge(0.15, Status::Discharging)));
+        assert!(!trigger.check(&charge(0.15, Status::Charging)));
+        assert!(trigger.check(&charge(0.15, Status::Discharging)));
+    }
+
+    #[test]
+    fn rearms_above_hysteresis() {
+        let mut trigger = Trigger::new(0.2);
+        assert!(trigger.check(&charge(0.2, Status::Discharging)));
+        // Hovering around the level doesn't fire again.
+        assert!(!trigger.check(&charge(0.21, Status::Discharging)));
+        assert!(!trigger.check(&charge(0.2, Status::Discharging)));
+        assert!(!trigger.check(&charge(0.23, Status::Discharging)));
+        assert!(trigger.check(&charge(0.2, Status::Discharging)));
+    }
+
+    #[test]
+    fn notifies_once_for_several_levels() {
+        let mut actions = Actions::new(&[0.2, 0.1], Some(0.05), vec![]);
+        assert_eq!(
+            actions.reached(&charge(0.5, Status::Discharging)),
+            (false, false)
+        );
+        assert_eq!(
+            actions.reached(&charge(0.08, Status::Discharging)),
+            (true, false)
+        );
+        assert_eq!(
+            actions.reached(&charge(0.07, Status::Discharging)),
+            (false, false)
+        );
+        assert_eq!(
+            actions.reached(&charge(0.05, Status::Discharging)),
+            (false, true)
+        );
+    }
+
+    /// A notification as received: what it replaces, summary, body and urgency.
+    type Received = (u32, String, String, Option<u8>);
+
+    struct Notifications {
+        received: mpsc::Sender<Received>,
+    }
+
+    #[interface(name = "org.freedesktop.Notifications")]
+    impl Notifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            body: &str,
+            _actions: Vec<&str>,
+            hints: HashMap<&str, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = hints
+                .get("urgency")
+                .and_then(|value| value.downcast_ref::<u8>().ok());
+            let _ = self
+                .received
+                .send((replaces_id, summary.into(), body.into(), urgency));
+            replaces_id + 1
+        }
+    }
+
+    #[test]
+    fn sends_notification() {
+        let bus = PrivateBus::start();
+        let (tx, rx) = mpsc::channel();
+        let _server = connection::Builder::address(bus.address())
+            .and_then(|builder| builder.name(NOTIFICATIONS))
+            .and_then(|builder| {
+                builder.serve_at(
+                    "/org/freedesktop/Notifications",
+                    Notifications { received: tx },
+                )
+            })
+            .and_then(|builder| builder.build())
+            .expect("Mock notification server should be on the bus");
+        let conn = bus.connect();
+
+        let mut actions = Actions::new(&[0.2], Some(0.05), vec![]);
+        let mut low = charge(0.2, Status::Discharging);
+        low.time_to_empty = Some(Duration::from_secs(75 * 60));
+        actions.notification = actions
+            .send(&conn, &low, false)
+            .expect("Notification should be sent");
+        assert_eq!(
+            rx.recv().unwrap(),
+            (
+                0,
+                "Battery low".into(),
+                "20% left, about 1h15".into(),
+                Some(1)
+            )
+        );
+
+        // The next one replaces it.
+        let critical = charge(0.05, Status::Discharging);
+        actions
+            .send(&conn, &critical, true)
+            .expect("Notification should be sent");
+        assert_eq!(
+            rx.recv().unwrap(),
+            (1, "Battery critical".into(), "5% left".into(), Some(2))
+        );
+    }
+}
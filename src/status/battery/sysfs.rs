@@ -0,0 +1,177 @@
+//! Charging status and charge thresholds, which the `battery`
+//! crate doesn't report, read from sysfs directly.
+
+use std::{fs, path::Path};
+
+use super::Status;
+
+pub const SYSFS: &str = "/sys";
+
+/// A system battery, as opposed to one in a peripheral.
+#[derive(Debug, PartialEq)]
+pub struct Supply {
+    pub status: Status,
+
+    /// Charge (from 0 to 1) below which charging starts
+    /// and at which it stops, if the firmware supports it.
+    pub start_threshold: Option<f64>,
+    pub end_threshold: Option<f64>,
+}
+
+/// List the system batteries, in sysfs mounted at `root`.
+pub fn batteries(root: &Path) -> Result<Vec<Supply>, String> {
+    let dir = root.join("class/power_supply");
+    let entries =
+        fs::read_dir(&dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    let mut supplies = vec![];
+    for path in paths {
+        // Peripherals (e.g. a mouse) have a device scope.
+        if read(&path, "type").as_deref() != Some("Battery")
+            || read(&path, "scope").as_deref() == Some("Device")
+        {
+            continue;
+        }
+        let status = match read(&path, "status").as_deref() {
+            Some("Charging") => Status::Charging,
+            Some("Discharging") => Status::Discharging,
+            Some("Full") => Status::Full,
+            Some("Not charging") => Status::NotCharging,
+            _ => Status::Unknown,
+        };
+        // Older ThinkPad drivers use their own names.
+        let threshold = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| read(&path, name)?.parse::<f64>().ok())
+                .map(|percent| percent / 100.)
+        };
+        supplies.push(Supply {
+            status,
+            start_threshold: threshold(&[
+                "charge_control_start_threshold",
+                "charge_start_threshold",
+            ]),
+            end_threshold: threshold(&["charge_control_end_threshold", "charge_stop_threshold"]),
+        });
+    }
+    Ok(supplies)
+}
+
+fn read(dir: &Path, name: &str) -> Option<String> {
+    let contents = fs::read_to_string(dir.join(name)).ok()?;
+    Some(contents.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write the files of a power supply.
+    fn supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+        let dir = root.join("class/power_supply").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), format!("{}\n", contents)).unwrap();
+        }
+    }
+
+    #[test]
+    fn lists_system_batteries() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        supply(
+            root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("scope", "System"),
+                ("status", "Not charging"),
+                ("charge_control_start_threshold", "40"),
+                ("charge_control_end_threshold", "80"),
+            ],
+        );
+        // Older ThinkPad drivers.
+        supply(
+            root,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("charge_start_threshold", "75"),
+                ("charge_stop_threshold", "90"),
+            ],
+        );
+        supply(
+            root,
+            "BAT2",
+            &[("type", "Battery"), ("status", "Discharging")],
+        );
+        supply(root, "AC", &[("type", "Mains"), ("online", "1")]);
+        supply(
+            root,
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+            ],
+        );
+
+        assert_eq!(
+            batteries(root),
+            Ok(vec![
+                Supply {
+                    status: Status::NotCharging,
+                    start_threshold: Some(0.4),
+                    end_threshold: Some(0.8),
+                },
+                Supply {
+                    status: Status::Unknown,
+                    start_threshold: Some(0.75),
+                    end_threshold: Some(0.9),
+                },
+                Supply {
+                    status: Status::Discharging,
+                    start_threshold: None,
+                    end_threshold: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn reads_statuses() {
+        let root = tempfile::tempdir().unwrap();
+        let statuses = ["Charging", "Discharging", "Full", "Not charging", "Unknown"];
+        for (i, status) in statuses.iter().enumerate() {
+            supply(
+                root.path(),
+                &format!("BAT{}", i),
+                &[("type", "Battery"), ("status", status)],
+            );
+        }
+        let statuses: Vec<_> = batteries(root.path())
+            .unwrap()
+            .into_iter()
+            .map(|supply| supply.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                Status::Charging,
+                Status::Discharging,
+                Status::Full,
+                Status::NotCharging,
+                Status::Unknown,
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_without_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(batteries(root.path()).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! The combined battery state from UPower's display device.
+
+use std::{collections::HashMap, time::Duration};
+
+use zbus::{
+    blocking::{fdo::PropertiesProxy, Connection},
+    message::Type,
+    names::InterfaceName,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue},
+    MatchRule,
+};
+
+use super::{Charge, Status};
+use crate::status::{dbus, Update};
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const DEVICE: &str = "org.freedesktop.UPower.Device";
+const DISPLAY_DEVICE: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+
+/// `Type` of devices which are batteries.
+const BATTERY: u32 = 2;
+
+/// Report the state of the display device, and again whenever
+/// one of its properties changes, e.g. when plugging in the charger.
+pub fn subscribe(conn: &Connection, update: Update<Option<Charge>>) -> zbus::Result<()> {
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(UPOWER)?
+        .path(DISPLAY_DEVICE)?
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .build();
+    dbus::follow(conn, rule, update, || {
+        query(conn).map_err(|err| format!("Failed to query UPower: {}", err))
+    })
+}
+
+fn query(conn: &Connection) -> zbus::Result<Option<Charge>> {
+    let device = properties(conn, ObjectPath::from_static_str_unchecked(DISPLAY_DEVICE))?;
+    let get = |name: &str| device.get(name);
+    let present = get("IsPresent")
+        .and_then(|value| value.downcast_ref::<bool>().ok())
+        .unwrap_or(false);
+    let kind = get("Type").and_then(|value| value.downcast_ref::<u32>().ok());
+    if !present || kind != Some(BATTERY) {
+        return Ok(None);
+    }
+
+    let percent = get("Percentage")
+        .and_then(|value| value.downcast_ref::<f64>().ok())
+        .ok_or(zbus::Error::Failure("No percentage reported".into()))?;
+    let status = match get("State").and_then(|value| value.downcast_ref::<u32>().ok()) {
+        Some(1) => Status::Charging,
+        Some(2) | Some(6) => Status::Discharging,
+        Some(3) => Status::Empty,
+        Some(4) => Status::Full,
+        // Pending charge, e.g. held by a charge threshold.
+        Some(5) => Status::NotCharging,
+        _ => Status::Unknown,
+    };
+    let (health, cycles) = wear(conn)?;
+    Ok(Some(Charge {
+        percent: percent / 100.,
+        status,
+        time_to_empty: time(&device, "TimeToEmpty"),
+        time_to_full: time(&device, "TimeToFull"),
+        health,
+        cycles,
+    }))
+}
+
+/// The combined health and the most charge cycles of the batteries
+/// behind the display device, which doesn't report them itself.
+fn wear(conn: &Connection) -> zbus::Result<(Option<f64>, Option<u32>)> {
+    let paths: Vec<OwnedObjectPath> = conn
+        .call_method(
+            Some(UPOWER),
+            UPOWER_PATH,
+            Some(UPOWER),
+            "EnumerateDevices",
+            &(),
+        )?
+        .body()
+        .deserialize()?;
+    let mut full = 0.;
+    let mut design = 0.;
+    let mut cycles = None;
+    for path in paths {
+        let device = properties(conn, path.as_ref())?;
+        let get = |name: &str| device.get(name);
+        let kind = get("Type").and_then(|value| value.downcast_ref::<u32>().ok());
+        // Peripherals like mice don't power the machine.
+        let power_supply = get("PowerSupply")
+            .and_then(|value| value.downcast_ref::<bool>().ok())
+            .unwrap_or(false);
+        if kind != Some(BATTERY) || !power_supply {
+            continue;
+        }
+        let energy = |name: &str| {
+            get(name)
+                .and_then(|value| value.downcast_ref::<f64>().ok())
+                .unwrap_or(0.)
+        };
+        full += energy("EnergyFull");
+        design += energy("EnergyFullDesign");
+        // Unknown counts are reported as -1, or not at all by older versions.
+        let count = get("ChargeCycles")
+            .and_then(|value| value.downcast_ref::<i32>().ok())
+            .and_then(|count| u32::try_from(count).ok());
+        cycles = cycles.max(count);
+    }
+    Ok(((design > 0.).then(|| full / design), cycles))
+}
+
+fn properties(
+    conn: &Connection,
+    path: ObjectPath<'_>,
+) -> zbus::Result<HashMap<String, OwnedValue>> {
+    let properties = PropertiesProxy::builder(conn)
+        .destination(UPOWER)?
+        .path(path)?
+        .build()?;
+    Ok(properties.get_all(InterfaceName::from_static_str_unchecked(DEVICE))?)
+}
+
+/// A duration in seconds, which UPower reports as zero when unknown.
+fn time(device: &HashMap<String, OwnedValue>, name: &str) -> Option<Duration> {
+    let seconds = device.get(name)?.downcast_ref::<i64>().ok()?;
+    (seconds > 0).then(|| Duration::from_secs(seconds as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use zbus::{blocking::connection, interface};
+
+    use super::*;
+    use crate::status::dbus::PrivateBus;
+
+    #[derive(Default)]
+    struct Device {
+        kind: u32,
+        power_supply: bool,
+        percentage: f64,
+        state: u32,
+        energy_full: f64,
+        energy_full_design: f64,
+        charge_cycles: i32,
+    }
+
+    #[interface(name = "org.freedesktop.UPower.Device")]
+    impl Device {
+        #[zbus(property)]
+        fn is_present(&self) -> bool {
+            self.kind != 0
+        }
+
+        #[zbus(property, name = "Type")]
+        fn kind(&self) -> u32 {
+            self.kind
+        }
+
+        #[zbus(property)]
+        fn power_supply(&self) -> bool {
+            self.power_supply
+        }
+
+        #[zbus(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[zbus(property)]
+        fn time_to_empty(&self) -> i64 {
+            if self.state == 2 {
+                5400
+            } else {
+                0
+            }
+        }
+
+        #[zbus(property)]
+        fn time_to_full(&self) -> i64 {
+            0
+        }
+
+        #[zbus(property)]
+        fn energy_full(&self) -> f64 {
+            self.energy_full
+        }
+
+        #[zbus(property)]
+        fn energy_full_design(&self) -> f64 {
+            self.energy_full_design
+        }
+
+        #[zbus(property)]
+        fn charge_cycles(&self) -> i32 {
+            self.charge_cycles
+        }
+    }
+
+    struct UPower {
+        devices: Vec<OwnedObjectPath>,
+    }
+
+    #[interface(name = "org.freedesktop.UPower")]
+    impl UPower {
+        fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+            self.devices.clone()
+        }
+    }
+
+    /// Serve a mock UPower with a display device and the devices behind it.
+    fn upower(bus: &PrivateBus, display: Device, devices: Vec<Device>) -> Connection {
+        let paths: Vec<_> = (0..devices.len())
+            .map(|i| {
+                OwnedObjectPath::try_from(format!("{}/devices/mock_{}", UPOWER_PATH, i)).unwrap()
+            })
+            .collect();
+        let mut builder = connection::Builder::address(bus.address())
+            .and_then(|builder| builder.name(UPOWER))
+            .and_then(|builder| builder.serve_at(DISPLAY_DEVICE, display))
+            .and_then(|builder| {
+                builder.serve_at(
+                    UPOWER_PATH,
+                    UPower {
+                        devices: paths.clone(),
+                    },
+                )
+            })
+            .expect("Mock UPower should be set up");
+        for (path, device) in paths.into_iter().zip(devices) {
+            builder = builder
+                .serve_at(path, device)
+                .expect("Mock device should be set up");
+        }
+        builder.build().expect("Mock UPower should be on the bus")
+    }
+
+    /// The first state reported.
+    fn charge(bus: &PrivateBus) -> Option<Charge> {
+        let (tx, rx) = mpsc::channel();
+        let conn = bus.connect();
+        thread::spawn(move || {
+            subscribe(&conn, &mut |charge| {
+                let _ = tx.send(charge);
+            })
+        });
+        rx.recv_timeout(Duration::from_secs(5))
+            .expect("A state should be reported")
+            .expect("The state should be known")
+    }
+
+    #[test]
+    fn reads_display_device() {
+        let bus = PrivateBus::start();
+        let display = Device {
+            kind: BATTERY,
+            percentage: 55.,
+            state: 2,
+            ..Device::default()
+        };
+        let _upower = upower(&bus, display, vec![]);
+        let charge = charge(&bus).expect("Should have a battery");
+        assert_eq!(charge.percent, 0.55);
+        assert_eq!(charge.status, Status::Discharging);
+        assert_eq!(charge.time_to_empty, Some(Duration::from_secs(5400)));
+        assert_eq!(charge.time_to_full, None);
+        assert_eq!(charge.health, None);
+        assert_eq!(charge.cycles, None);
+    }
+
+    #[test]
+    fn combines_wear_of_batteries() {
+        let bus = PrivateBus::start();
+        let display = Device {
+            kind: BATTERY,
+            percentage: 80.,
+            state: 4,
+            ..Device::default()
+        };
+        let battery = |full, design, cycles| Device {
+            kind: BATTERY,
+            power_supply: true,
+            energy_full: full,
+            energy_full_design: design,
+            charge_cycles: cycles,
+            ..Device::default()
+        };
+        let devices = vec![
+            battery(40., 50., 120),
+            battery(18., 20., -1),
+            // A mouse and a charger, which don't count.
+            Device {
+                kind: 5,
+                energy_full: 1.,
+                energy_full_design: 10.,
+                charge_cycles: 900,
+                ..Device::default()
+            },
+            Device {
+                kind: 1,
+                power_supply: true,
+                ..Device::default()
+            },
+        ];
+        let _upower = upower(&bus, display, devices);
+        let charge = charge(&bus).expect("Should have a battery");
+        assert_eq!(charge.status, Status::Full);
+        assert_eq!(charge.health, Some(58. / 70.));
+        assert_eq!(charge.cycles, Some(120));
+    }
+
+    #[test]
+    fn reports_no_battery() {
+        let bus = PrivateBus::start();
+        let _upower = upower(&bus, Device::default(), vec![]);
+        assert!(charge(&bus).is_none());
+    }
+}
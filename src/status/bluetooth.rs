@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use zbus::{
+    blocking::{fdo::ObjectManagerProxy, Connection},
+    message::Type,
+    zvariant::OwnedValue,
+    MatchRule,
+};
+
+use super::{dbus, Bar, Indicator, Redraw, Update, Watch};
+use crate::config::Colors;
+
+const BLUEZ: &str = "org.bluez";
+const ADAPTER: &str = "org.bluez.Adapter1";
+const DEVICE: &str = "org.bluez.Device1";
+const BATTERY: &str = "org.bluez.Battery1";
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Scale the bar by the number of connected devices.
+    show_count: bool,
+
+    /// Number of connected devices that fills the bar.
+    max_count: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            show_count: false,
+            max_count: 3,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatteryOptions {
+    /// Charge at or below which the bar turns urgent.
+    threshold: f64,
+}
+
+impl Default for BatteryOptions {
+    fn default() -> Self {
+        BatteryOptions { threshold: 0.2 }
+    }
+}
+
+/// State of the bluetooth adapters and devices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bluez {
+    powered: bool,
+    connected: usize,
+
+    /// Lowest charge among the connected devices
+    /// which report one, from 0 to 1.
+    battery: Option<f64>,
+}
+
+/// A color representing the bluetooth adapter state,
+/// and whether any devices are connected.
+pub struct Bluetooth {
+    colors: Colors,
+    options: Options,
+    bluez: Watch<Bluez>,
+}
+
+impl Bluetooth {
+    pub fn new(colors: Colors, options: Options, redraw: Redraw) -> Self {
+        Bluetooth {
+            colors,
+            options,
+            bluez: Watch::spawn_retrying(redraw, watch),
+        }
+    }
+}
+
+impl Indicator for Bluetooth {
+    fn name(&self) -> &'static str {
+        "bluetooth"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        self.bluez.check()
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let bluez = self.bluez.get()?;
+        let bar = if bluez.connected > 0 {
+            let percent = if self.options.show_count {
+                bluez.connected as f64 / self.options.max_count.max(1) as f64
+            } else {
+                1.0
+            };
+            (percent, self.colors.ok)
+        } else if bluez.powered {
+            (1.0, self.colors.normal)
+        } else {
+            (1.0, self.colors.bg)
+        };
+        Some(bar)
+    }
+}
+
+/// A bar representing the lowest battery level
+/// among the connected bluetooth devices.
+pub struct BluetoothBattery {
+    colors: Colors,
+    options: BatteryOptions,
+    bluez: Watch<Bluez>,
+}
+
+impl BluetoothBattery {
+    pub fn new(colors: Colors, options: BatteryOptions, redraw: Redraw) -> Self {
+        BluetoothBattery {
+            colors,
+            options,
+            bluez: Watch::spawn_retrying(redraw, watch),
+        }
+    }
+}
+
+impl Indicator for BluetoothBattery {
+    fn name(&self) -> &'static str {
+        "bluetooth_battery"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        self.bluez.check()
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let percent = self.bluez.get()?.battery?;
+        let color = if percent <= self.options.threshold {
+            self.colors.urgent
+        } else {
+            self.colors.normal
+        };
+        Some((percent, color))
+    }
+}
+
+fn watch(update: Update<Bluez>) -> Result<(), String> {
+    Connection::system()
+        .and_then(|conn| subscribe(&conn, update))
+        .map_err(|err| err.to_string())
+}
+
+/// Report the state of BlueZ, and again whenever it signals a change.
+fn subscribe(conn: &Connection, update: Update<Bluez>) -> zbus::Result<()> {
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(BLUEZ)?
+        .build();
+    let manager = ObjectManagerProxy::builder(conn)
+        .destination(BLUEZ)?
+        .path("/")?
+        .build()?;
+    dbus::follow(conn, rule, update, || query(&manager))
+}
+
+fn query(manager: &ObjectManagerProxy) -> Result<Bluez, String> {
+    let objects = manager
+        .get_managed_objects()
+        .map_err(|err| format!("Failed to query BlueZ: {}", err))?;
+    let flag = |props: &HashMap<String, OwnedValue>, name: &str| {
+        props
+            .get(name)
+            .and_then(|value| value.downcast_ref::<bool>().ok())
+            .unwrap_or(false)
+    };
+    let powered = objects
+        .values()
+        .filter_map(|interfaces| interfaces.get(ADAPTER))
+        .any(|adapter| flag(adapter, "Powered"));
+    let connected: Vec<_> = objects
+        .values()
+        .filter(|interfaces| {
+            interfaces
+                .get(DEVICE)
+                .is_some_and(|device| flag(device, "Connected"))
+        })
+        .collect();
+    let battery = connected
+        .iter()
+        .filter_map(|interfaces| interfaces.get(BATTERY)?.get("Percentage"))
+        .filter_map(|percent| percent.downcast_ref::<u8>().ok())
+        .min()
+        .map(|percent| percent as f64 / 100.);
+    Ok(Bluez {
+        powered,
+        connected: connected.len(),
+        battery,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use zbus::{blocking::connection, fdo::ObjectManager, interface};
+
+    use super::*;
+    use crate::status::dbus::PrivateBus;
+
+    struct Adapter {
+        powered: bool,
+    }
+
+    #[interface(name = "org.bluez.Adapter1")]
+    impl Adapter {
+        #[zbus(property)]
+        fn powered(&self) -> bool {
+            self.powered
+        }
+    }
+
+    struct Device {
+        connected: bool,
+    }
+
+    #[interface(name = "org.bluez.Device1")]
+    impl Device {
+        #[zbus(property)]
+        fn connected(&self) -> bool {
+            self.connected
+        }
+    }
+
+    struct Battery {
+        percentage: u8,
+    }
+
+    #[interface(name = "org.bluez.Battery1")]
+    impl Battery {
+        #[zbus(property)]
+        fn percentage(&self) -> u8 {
+            self.percentage
+        }
+    }
+
+    /// Serve a mock BlueZ with an adapter and devices,
+    /// each with whether it's connected and its battery.
+    fn bluez(bus: &PrivateBus, powered: bool, devices: &[(bool, Option<u8>)]) -> Connection {
+        let mut builder = connection::Builder::address(bus.address())
+            .and_then(|builder| builder.name(BLUEZ))
+            .and_then(|builder| builder.serve_at("/", ObjectManager))
+            .and_then(|builder| builder.serve_at("/org/bluez/hci0", Adapter { powered }))
+            .expect("Mock BlueZ should be set up");
+        for (i, (connected, battery)) in devices.iter().enumerate() {
+            let path = format!("/org/bluez/hci0/dev_{}", i);
+            builder = builder
+                .serve_at(
+                    path.clone(),
+                    Device {
+                        connected: *connected,
+                    },
+                )
+                .expect("Mock device should be set up");
+            if let Some(percentage) = battery {
+                builder = builder
+                    .serve_at(
+                        path.clone(),
+                        Battery {
+                            percentage: *percentage,
+                        },
+                    )
+                    .expect("Mock battery should be set up");
+            }
+        }
+        builder.build().expect("Mock BlueZ should be on the bus")
+    }
+
+    /// Watch BlueZ on the bus, returning the states as they're reported.
+    fn watch(bus: &PrivateBus) -> mpsc::Receiver<Result<Bluez, String>> {
+        let (tx, rx) = mpsc::channel();
+        let conn = bus.connect();
+        thread::spawn(move || {
+            subscribe(&conn, &mut |bluez| {
+                let _ = tx.send(bluez);
+            })
+        });
+        rx
+    }
+
+    /// Wait until the expected state is reported.
+    fn expect(states: &mpsc::Receiver<Result<Bluez, String>>, expected: Bluez) {
+        let mut last = None;
+        while let Ok(state) = states.recv_timeout(Duration::from_secs(5)) {
+            if state == Ok(expected) {
+                return;
+            }
+            last = Some(state);
+        }
+        panic!("Expected {:?}, last got {:?}", expected, last);
+    }
+
+    #[test]
+    fn reports_powered_adapter() {
+        let bus = PrivateBus::start();
+        let _bluez = bluez(&bus, true, &[]);
+        expect(
+            &watch(&bus),
+            Bluez {
+                powered: true,
+                connected: 0,
+                battery: None,
+            },
+        );
+    }
+
+    #[test]
+    fn counts_connected_devices() {
+        let bus = PrivateBus::start();
+        let _bluez = bluez(&bus, true, &[(true, None), (false, None), (true, None)]);
+        expect(
+            &watch(&bus),
+            Bluez {
+                powered: true,
+                connected: 2,
+                battery: None,
+            },
+        );
+    }
+
+    #[test]
+    fn reports_lowest_battery_of_connected_devices() {
+        let bus = PrivateBus::start();
+        let devices = [
+            (true, Some(80)),
+            (false, Some(10)),
+            (true, Some(40)),
+            (true, None),
+        ];
+        let _bluez = bluez(&bus, true, &devices);
+        expect(
+            &watch(&bus),
+            Bluez {
+                powered: true,
+                connected: 3,
+                battery: Some(0.4),
+            },
+        );
+    }
+
+    #[test]
+    fn follows_changes() {
+        let bus = PrivateBus::start();
+        let bluez = bluez(&bus, false, &[(false, Some(50))]);
+        let states = watch(&bus);
+        expect(
+            &states,
+            Bluez {
+                powered: false,
+                connected: 0,
+                battery: None,
+            },
+        );
+
+        let adapter = bluez
+            .object_server()
+            .interface::<_, Adapter>("/org/bluez/hci0")
+            .expect("Mock adapter should be served");
+        adapter.get_mut().powered = true;
+        zbus::block_on(adapter.get().powered_changed(adapter.signal_emitter()))
+            .expect("Change should be signalled");
+        expect(
+            &states,
+            Bluez {
+                powered: true,
+                connected: 0,
+                battery: None,
+            },
+        );
+
+        let device = bluez
+            .object_server()
+            .interface::<_, Device>("/org/bluez/hci0/dev_0")
+            .expect("Mock device should be served");
+        device.get_mut().connected = true;
+        zbus::block_on(device.get().connected_changed(device.signal_emitter()))
+            .expect("Change should be signalled");
+        expect(
+            &states,
+            Bluez {
+                powered: true,
+                connected: 1,
+                battery: Some(0.5),
+            },
+        );
+    }
+}
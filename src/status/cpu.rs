@@ -0,0 +1,251 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use super::{
+    ramp::{Direction, Ramp},
+    Bar, Indicator, Overlay,
+};
+use crate::config::Colors;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Usage at or above which the bar turns to a warning.
+    warn: f64,
+
+    /// Usage at or above which the bar turns urgent.
+    urgent: f64,
+
+    /// Split the segment into a thin bar per core.
+    per_core: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            warn: 0.7,
+            urgent: 0.9,
+            per_core: false,
+        }
+    }
+}
+
+/// Time spent by a CPU since boot, in clock ticks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Times {
+    busy: u64,
+    total: u64,
+}
+
+impl Times {
+    /// Usage from 0 to 1 since an earlier reading.
+    fn usage_since(&self, earlier: &Times) -> f64 {
+        let total = self.total.saturating_sub(earlier.total);
+        if total == 0 {
+            return 0.;
+        }
+        self.busy.saturating_sub(earlier.busy) as f64 / total as f64
+    }
+}
+
+/// A bar representing the CPU usage since the last refresh.
+pub struct Cpu {
+    colors: Colors,
+    options: Options,
+    levels: Ramp,
+
+    /// The last reading, for all CPUs and then each core.
+    times: Option<(Times, Vec<Times>)>,
+    usage: Option<(f64, Vec<f64>)>,
+}
+
+impl Cpu {
+    pub fn new(colors: Colors, options: Options) -> Self {
+        let levels = Ramp::new(
+            vec![(options.warn, colors.warn), (options.urgent, colors.urgent)],
+            Direction::Rising,
+            false,
+        );
+        Cpu {
+            colors,
+            options,
+            levels,
+            times: None,
+            usage: None,
+        }
+    }
+}
+
+impl Indicator for Cpu {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let stat = fs::read_to_string("/proc/stat")
+            .map_err(|err| format!("Failed to read /proc/stat: {}", err))?;
+        let (total, cores) = times(&stat)?;
+        if let Some((last_total, last_cores)) = &self.times {
+            let usage = total.usage_since(last_total);
+            let cores = cores
+                .iter()
+                .zip(last_cores)
+                .map(|(core, last)| core.usage_since(last))
+                .collect();
+            self.usage = Some((usage, cores));
+        }
+        self.times = Some((total, cores));
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        if self.options.per_core {
+            return None;
+        }
+        let (usage, _) = self.usage.as_ref()?;
+        Some((*usage, self.levels.color(*usage, self.colors.normal)))
+    }
+
+    fn overlays(&self) -> Vec<Overlay> {
+        let Some((_, cores)) = self.usage.as_ref().filter(|_| self.options.per_core) else {
+            return vec![];
+        };
+        let share = 1. / cores.len() as f64;
+        cores
+            .iter()
+            .enumerate()
+            .map(|(i, usage)| {
+                (
+                    i as f64 * share,
+                    (usage * share, self.levels.color(*usage, self.colors.normal)),
+                )
+            })
+            .collect()
+    }
+
+    fn details(&self) -> Option<String> {
+        let (usage, cores) = self.usage.as_ref()?;
+        let cores: Vec<_> = cores
+            .iter()
+            .map(|usage| format!("{:.0}%", usage * 100.))
+            .collect();
+        Some(format!("CPU {:.0}% ({})", usage * 100., cores.join(" ")))
+    }
+}
+
+/// The times of all CPUs together, and of each core, from `/proc/stat`.
+fn times(stat: &str) -> Result<(Times, Vec<Times>), String> {
+    let mut total = None;
+    let mut cores = vec![];
+    for line in stat.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next().filter(|name| name.starts_with("cpu")) else {
+            continue;
+        };
+        // user nice system idle iowait irq softirq steal, then guest
+        // times which are already counted in user and nice.
+        let ticks: Vec<u64> = fields.take(8).filter_map(|n| n.parse().ok()).collect();
+        if ticks.len() < 5 {
+            return Err(format!("Invalid line in /proc/stat: {}", line));
+        }
+        let idle = ticks[3] + ticks[4];
+        let sum: u64 = ticks.iter().sum();
+        let times = Times {
+            busy: sum - idle,
+            total: sum,
+        };
+        if name == "cpu" {
+            total = Some(times);
+        } else {
+            cores.push(times);
+        }
+    }
+    let total = total.ok_or("No CPU times in /proc/stat")?;
+    Ok((total, cores))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "\
+cpu  400 10 90 1400 100 0 0 0 0 0
+cpu0 300 10 40 600 50 0 0 0 0 0
+cpu1 100 0 50 800 50 0 0 0 0 0
+intr 12345 0 0
+ctxt 67890
+btime 1700000000
+";
+
+    #[test]
+    fn reads_total_and_cores() {
+        let (total, cores) = times(STAT).unwrap();
+        assert_eq!(
+            total,
+            Times {
+                busy: 500,
+                total: 2000,
+            }
+        );
+        assert_eq!(
+            cores,
+            [
+                Times {
+                    busy: 350,
+                    total: 1000,
+                },
+                Times {
+                    busy: 150,
+                    total: 1000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_iowait_as_idle() {
+        let (total, _) = times("cpu  10 0 0 50 40 0 0 0\n").unwrap();
+        assert_eq!(
+            total,
+            Times {
+                busy: 10,
+                total: 100
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_guest_times() {
+        // Guest times are already counted in user and nice.
+        let (total, _) = times("cpu  30 0 0 70 0 0 0 0 20 10\n").unwrap();
+        assert_eq!(
+            total,
+            Times {
+                busy: 30,
+                total: 100
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_missing_total() {
+        assert!(times("cpu0 1 2 3 4 5\n").is_err());
+        assert!(times("cpu 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn measures_usage_since() {
+        let earlier = Times {
+            busy: 100,
+            total: 1000,
+        };
+        let now = Times {
+            busy: 175,
+            total: 1100,
+        };
+        assert_eq!(now.usage_since(&earlier), 0.75);
+        // No ticks since, e.g. polled twice in a row.
+        assert_eq!(earlier.usage_since(&earlier), 0.);
+    }
+}
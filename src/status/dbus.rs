@@ -0,0 +1,84 @@
+//! Helpers for the indicators watching services over D-Bus.
+
+use zbus::{
+    blocking::{Connection, MessageIterator},
+    MatchRule,
+};
+
+use super::Update;
+
+/// Report the state of a service as given by `query`, and again
+/// after each of its signals matching `rule`.
+/// Blocks until the connection is lost.
+pub fn follow<T>(
+    conn: &Connection,
+    rule: MatchRule<'_>,
+    update: Update<T>,
+    query: impl Fn() -> Result<T, String>,
+) -> zbus::Result<()> {
+    // Subscribe before the initial query so no change is missed.
+    let signals = MessageIterator::for_match_rule(rule, conn, None)?;
+    update(query());
+    for msg in signals {
+        msg?;
+        update(query());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub use private_bus::PrivateBus;
+
+#[cfg(test)]
+mod private_bus {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+    };
+
+    use zbus::blocking::{connection, Connection};
+
+    /// A `dbus-daemon` of its own for tests, stopped when dropped.
+    pub struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        pub fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--address=unix:tmpdir=/tmp"])
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("dbus-daemon should be installed");
+            let stdout = daemon.stdout.take().expect("Stdout should be piped");
+            let mut address = String::new();
+            BufReader::new(stdout)
+                .read_line(&mut address)
+                .expect("dbus-daemon should print its address");
+            PrivateBus {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+
+        pub fn address(&self) -> &str {
+            &self.address
+        }
+
+        pub fn connect(&self) -> Connection {
+            connection::Builder::address(self.address())
+                .and_then(|builder| builder.build())
+                .expect("Should connect to the private bus")
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+}
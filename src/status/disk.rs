@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use rustix::fs::statvfs;
+use serde::Deserialize;
+
+use super::{
+    ramp::{Direction, Ramp},
+    Bar, Indicator,
+};
+use crate::config::Colors;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Mount point of the filesystem to show.
+    mount: PathBuf,
+
+    /// Fullness at or above which the bar turns to a warning.
+    warn: f64,
+
+    /// Fullness at or above which the bar turns urgent.
+    urgent: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mount: PathBuf::from("/"),
+            warn: 0.8,
+            urgent: 0.9,
+        }
+    }
+}
+
+/// Space on a filesystem.
+#[derive(Debug, Clone, Copy)]
+struct Space {
+    /// From 0 to 1, as reported by `df`.
+    used: f64,
+
+    /// Available to unprivileged users, in bytes.
+    available: u64,
+}
+
+/// A bar representing how full a filesystem is.
+pub struct Disk {
+    colors: Colors,
+    options: Options,
+    levels: Ramp,
+    space: Option<Space>,
+}
+
+impl Disk {
+    pub fn new(colors: Colors, options: Options) -> Self {
+        let levels = Ramp::new(
+            vec![(options.warn, colors.warn), (options.urgent, colors.urgent)],
+            Direction::Rising,
+            false,
+        );
+        Disk {
+            colors,
+            options,
+            levels,
+            space: None,
+        }
+    }
+}
+
+impl Indicator for Disk {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let mount = &self.options.mount;
+        let stat = statvfs(mount)
+            .map_err(|err| format!("Failed to get space on {}: {}", mount.display(), err))?;
+        // Blocks reserved for root count as neither used nor available.
+        let used = stat.f_blocks.saturating_sub(stat.f_bfree);
+        let usable = used + stat.f_bavail;
+        self.space = Some(Space {
+            used: if usable > 0 {
+                used as f64 / usable as f64
+            } else {
+                0.
+            },
+            available: stat.f_bavail * stat.f_frsize,
+        });
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let used = self.space?.used;
+        Some((used, self.levels.color(used, self.colors.normal)))
+    }
+
+    fn details(&self) -> Option<String> {
+        let space = self.space?;
+        let gib = space.available as f64 / (1024. * 1024. * 1024.);
+        Some(format!(
+            "{} {:.0}% full, {:.1} GiB free",
+            self.options.mount.display(),
+            space.used * 100.,
+            gib
+        ))
+    }
+}
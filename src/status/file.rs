@@ -0,0 +1,312 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use inotify::{Inotify, WatchMask};
+use regex_lite::Regex;
+use serde::Deserialize;
+
+use super::{Bar, Indicator, Redraw, Rgba, Update, Watch};
+use crate::config::Colors;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Options {
+    /// File to watch.
+    path: PathBuf,
+
+    /// Numeric contents which fill the bar.
+    #[serde(default = "max_value")]
+    max_value: f64,
+
+    /// Tried in order, the first one matching the contents wins.
+    #[serde(default)]
+    rules: Vec<RuleOptions>,
+}
+
+fn max_value() -> f64 {
+    100.
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleOptions {
+    /// Regex the contents have to match.
+    pattern: Option<String>,
+
+    /// Bounds (inclusive) the numeric contents have to be within.
+    min: Option<f64>,
+    max: Option<f64>,
+
+    /// A palette name, or `#rrggbb(aa)`.
+    color: String,
+
+    /// Height of the bar. By default numeric contents are
+    /// scaled against `max_value`, and anything else fills it.
+    height: Option<f64>,
+}
+
+/// A rule with its pattern and color parsed.
+struct Rule {
+    pattern: Option<Regex>,
+    min: Option<f64>,
+    max: Option<f64>,
+    color: Rgba,
+    height: Option<f64>,
+}
+
+impl Rule {
+    fn new(options: RuleOptions, colors: &Colors) -> Result<Self, String> {
+        let pattern = options
+            .pattern
+            .map(|pattern| Regex::new(&pattern))
+            .transpose()
+            .map_err(|err| format!("Invalid rule pattern: {}", err))?;
+        Ok(Rule {
+            pattern,
+            min: options.min,
+            max: options.max,
+            color: colors
+                .resolve(&options.color)
+                .map_err(|err| format!("Invalid rule: {}", err))?,
+            height: options.height,
+        })
+    }
+
+    fn matches(&self, contents: &str, number: Option<f64>) -> bool {
+        let pattern = self
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(contents));
+        let bounded = self.min.is_some() || self.max.is_some();
+        let within = number.is_some_and(|number| {
+            self.min.is_none_or(|min| number >= min) && self.max.is_none_or(|max| number <= max)
+        });
+        pattern && (!bounded || within)
+    }
+}
+
+/// A bar set by the contents of a file, which some
+/// other script writes to. Redrawn as soon as it changes.
+pub struct StateFile {
+    max_value: f64,
+    rules: Vec<Rule>,
+    contents: Watch<Option<String>>,
+}
+
+impl StateFile {
+    pub fn new(colors: Colors, options: Options, redraw: Redraw) -> Result<Self, String> {
+        let rules = options
+            .rules
+            .into_iter()
+            .map(|rule| Rule::new(rule, &colors))
+            .collect::<Result<_, _>>()?;
+        let path = options.path;
+        Ok(StateFile {
+            max_value: options.max_value,
+            rules,
+            contents: Watch::spawn_retrying(redraw, move |update| subscribe(&path, update)),
+        })
+    }
+}
+
+impl Indicator for StateFile {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        self.contents.check()
+    }
+
+    fn value(&self) -> Option<Bar> {
+        bar(&self.rules, self.max_value, &self.contents.get()??)
+    }
+}
+
+/// The bar set by the first rule matching the contents, if any.
+fn bar(rules: &[Rule], max_value: f64, contents: &str) -> Option<Bar> {
+    let contents = contents.trim();
+    let number = contents.parse::<f64>().ok();
+    let rule = rules.iter().find(|rule| rule.matches(contents, number))?;
+    let height = rule
+        .height
+        .or(number.map(|number| number / max_value))
+        .unwrap_or(1.0);
+    Some((height.clamp(0., 1.), rule.color))
+}
+
+/// Report the contents of the file (`None` if it doesn't exist),
+/// and again whenever it's written, replaced or removed.
+fn subscribe(path: &Path, update: Update<Option<String>>) -> Result<(), String> {
+    let err = |err: io::Error| format!("Failed to watch {}: {}", path.display(), err);
+
+    // Watch the directory rather than the file, to notice it being
+    // created or atomically replaced. Only complete writes are
+    // considered, so the file isn't read while being truncated.
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path.file_name();
+    let mut inotify = Inotify::init().map_err(err)?;
+    inotify
+        .watches()
+        .add(
+            dir,
+            WatchMask::CLOSE_WRITE
+                | WatchMask::CREATE
+                | WatchMask::MOVED_TO
+                | WatchMask::MOVED_FROM
+                | WatchMask::DELETE,
+        )
+        .map_err(err)?;
+
+    update(read(path));
+    let mut buffer = [0; 4096];
+    loop {
+        let events = inotify.read_events_blocking(&mut buffer).map_err(err)?;
+        if events.into_iter().any(|event| event.name == name) {
+            update(read(path));
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Failed to read {}: {}", path.display(), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::*;
+
+    const COLORS: Colors = Colors {
+        urgent: [1., 0., 0., 1.],
+        warn: [1., 1., 0., 1.],
+        ok: [0., 1., 0., 1.],
+        bg: [0., 0., 0., 1.],
+        mute: [0.5, 0.5, 0.5, 1.],
+        normal: [0., 0., 1., 1.],
+    };
+
+    /// Rules and the maximum value from the options of a segment.
+    fn rules(options: &str) -> (Vec<Rule>, f64) {
+        let options: Options = toml::from_str(&format!("path = \"/tmp/state\"\n{}", options))
+            .expect("Options should be valid");
+        let rules = options
+            .rules
+            .into_iter()
+            .map(|rule| Rule::new(rule, &COLORS))
+            .collect::<Result<_, _>>()
+            .expect("Rules should be valid");
+        (rules, options.max_value)
+    }
+
+    fn value(options: &str, contents: &str) -> Option<Bar> {
+        let (rules, max_value) = rules(options);
+        bar(&rules, max_value, contents)
+    }
+
+    #[test]
+    fn matches_pattern() {
+        let options = r#"rules = [{ pattern = "^up", color = "ok" }]"#;
+        assert_eq!(value(options, "up wg0\n"), Some((1., COLORS.ok)));
+        assert_eq!(value(options, "down"), None);
+    }
+
+    #[test]
+    fn matches_bounds() {
+        let options = r#"rules = [
+            { max = 20, color = "urgent" },
+            { min = 20, max = 50, color = "warn" },
+            { min = 50, color = "ok" },
+        ]"#;
+        assert_eq!(value(options, "10"), Some((0.1, COLORS.urgent)));
+        // Bounds are inclusive, and the first matching rule wins.
+        assert_eq!(value(options, "20"), Some((0.2, COLORS.urgent)));
+        assert_eq!(value(options, "35\n"), Some((0.35, COLORS.warn)));
+        assert_eq!(value(options, "80"), Some((0.8, COLORS.ok)));
+        // Bounds never match contents which aren't numeric.
+        assert_eq!(value(options, "full"), None);
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let options = r##"rules = [
+            { pattern = "^up", color = "ok" },
+            { pattern = "^up", color = "urgent" },
+            { color = "#ff8800" },
+        ]"##;
+        assert_eq!(value(options, "up"), Some((1., COLORS.ok)));
+        assert_eq!(
+            value(options, "down"),
+            Some((1., [1., 136. / 255., 0., 1.]))
+        );
+    }
+
+    #[test]
+    fn scales_height() {
+        let options = r#"
+            max_value = 200
+            rules = [{ pattern = "^1", color = "warn", height = 0.5 }, { color = "normal" }]
+        "#;
+        // A fixed height wins over scaling.
+        assert_eq!(value(options, "150"), Some((0.5, COLORS.warn)));
+        assert_eq!(value(options, "50"), Some((0.25, COLORS.normal)));
+        // Clamped to the segment.
+        assert_eq!(value(options, "400"), Some((1., COLORS.normal)));
+        assert_eq!(value(options, "-5"), Some((0., COLORS.normal)));
+        // Anything else fills it.
+        assert_eq!(value(options, "on"), Some((1., COLORS.normal)));
+    }
+
+    #[test]
+    fn hidden_without_rules() {
+        assert_eq!(value("", "up"), None);
+    }
+
+    /// Wait until the expected contents are reported.
+    fn expect(states: &mpsc::Receiver<Result<Option<String>, String>>, expected: Option<&str>) {
+        let mut last = None;
+        while let Ok(state) = states.recv_timeout(Duration::from_secs(5)) {
+            if state == Ok(expected.map(String::from)) {
+                return;
+            }
+            last = Some(state);
+        }
+        panic!("Expected {:?}, last got {:?}", expected, last);
+    }
+
+    #[test]
+    fn follows_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+        let (tx, rx) = mpsc::channel();
+        let watched = path.clone();
+        thread::spawn(move || {
+            subscribe(&watched, &mut |contents| {
+                let _ = tx.send(contents);
+            })
+        });
+        expect(&rx, None);
+
+        fs::write(&path, "up").unwrap();
+        expect(&rx, Some("up"));
+
+        // Replaced atomically, like most editors and scripts do.
+        let temporary = dir.path().join("state.tmp");
+        fs::write(&temporary, "down").unwrap();
+        fs::rename(&temporary, &path).unwrap();
+        expect(&rx, Some("down"));
+
+        fs::remove_file(&path).unwrap();
+        expect(&rx, None);
+    }
+}
@@ -0,0 +1,147 @@
+mod hyprland;
+mod river;
+mod sway;
+
+use std::{fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use super::{Bar, Indicator, Redraw, Update, Watch};
+use crate::config::Colors;
+
+/// Where to get the layout state from.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// A file written by some other script, containing `on` when fullscreen.
+    File,
+    Sway,
+    I3,
+    Hyprland,
+    River,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    backend: Backend,
+
+    /// File read by the `file` backend.
+    path: PathBuf,
+
+    /// Layouts which count as monocle, matched as substrings
+    /// of the layout name (sway/i3 and river).
+    monocle_layouts: Vec<String>,
+
+    /// Command printing the layout name whenever it changes (river).
+    river_command: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            backend: Backend::File,
+            path: PathBuf::from("/tmp/ws_fs"),
+            monocle_layouts: ["monocle", "tabbed", "stacked"].map(String::from).to_vec(),
+            river_command: ["ristate", "--layout"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl Options {
+    /// Whether a layout name counts as monocle.
+    fn is_monocle(&self, layout: &str) -> bool {
+        self.monocle_layouts
+            .iter()
+            .any(|monocle| layout.contains(monocle.as_str()))
+    }
+}
+
+/// Where the state is read from.
+enum Source {
+    /// Polled on every refresh.
+    File {
+        path: PathBuf,
+        fullscreen: Option<bool>,
+    },
+
+    /// Kept up to date by the compositor's events.
+    Compositor(Watch<bool>),
+}
+
+/// A color representing if the current layout is monocle (fake fullscreen)
+/// or the focused window is fullscreen.
+pub struct Layout {
+    colors: Colors,
+    source: Source,
+}
+
+impl Layout {
+    pub fn new(colors: Colors, options: Options, redraw: Redraw) -> Self {
+        let source = match options.backend {
+            Backend::File => Source::File {
+                path: options.path,
+                fullscreen: None,
+            },
+            _ => Source::Compositor(Watch::spawn_retrying(redraw, move |update| {
+                subscribe(&options, update)
+            })),
+        };
+        Layout { colors, source }
+    }
+}
+
+impl Indicator for Layout {
+    fn name(&self) -> &'static str {
+        "layout"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        match &mut self.source {
+            Source::File { path, fullscreen } => {
+                let contents = fs::read_to_string(&*path)
+                    .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+                *fullscreen = Some(contents.contains("on"));
+                Ok(())
+            }
+            Source::Compositor(watch) => watch.check(),
+        }
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let fullscreen = match &self.source {
+            Source::File { fullscreen, .. } => (*fullscreen)?,
+            Source::Compositor(watch) => watch.get()?,
+        };
+        let color = if fullscreen {
+            self.colors.warn
+        } else {
+            self.colors.bg
+        };
+        Some((1.0, color))
+    }
+}
+
+fn subscribe(options: &Options, update: Update<bool>) -> Result<(), String> {
+    // Only report changes, as some events (e.g. window titles) are frequent.
+    let mut last = None;
+    let mut update = |result: Result<bool, String>| {
+        if let Ok(fullscreen) = result {
+            if last.replace(fullscreen) == Some(fullscreen) {
+                return;
+            }
+        } else {
+            last = None;
+        }
+        update(result);
+    };
+    match options.backend {
+        Backend::Sway | Backend::I3 => sway::socket(options.backend)
+            .and_then(|path| sway::subscribe(&path, options, &mut update)),
+        Backend::Hyprland => {
+            hyprland::socket_dir().and_then(|dir| hyprland::subscribe(&dir, &mut update))
+        }
+        Backend::River => river::subscribe(options, &mut update),
+        Backend::File => unreachable!("Files are polled"),
+    }
+}
@@ -0,0 +1,130 @@
+//! Fullscreen workspaces from Hyprland's sockets.
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::status::Update;
+
+/// Events after which the active workspace may have changed.
+const EVENTS: &[&str] = &[
+    "fullscreen",
+    "workspace",
+    "focusedmon",
+    "openwindow",
+    "closewindow",
+    "movewindow",
+];
+
+/// Report whether the active workspace has a fullscreen
+/// window, and again after every relevant event.
+/// Blocks until the connection is lost.
+pub fn subscribe(dir: &Path, update: Update<bool>) -> Result<(), String> {
+    let path = dir.join(".socket2.sock");
+    let events = UnixStream::connect(&path)
+        .map_err(|err| format!("Failed to connect to {}: {}", path.display(), err))?;
+
+    update(query(dir));
+    for line in BufReader::new(events).lines() {
+        let line = line.map_err(|err| format!("Failed to read Hyprland event: {}", err))?;
+        let event = line
+            .split_once(">>")
+            .map_or(line.as_str(), |(event, _)| event);
+        if EVENTS.contains(&event) {
+            update(query(dir));
+        }
+    }
+    Err("Hyprland closed the connection".into())
+}
+
+/// Directory of the sockets of the running instance.
+pub fn socket_dir() -> Result<PathBuf, String> {
+    let instance = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .map_err(|_| "HYPRLAND_INSTANCE_SIGNATURE is not set".to_string())?;
+    let runtime = env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("hypr").join(&instance))
+        .filter(|dir| dir.exists());
+    // Older versions keep them in /tmp.
+    Ok(runtime.unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&instance)))
+}
+
+fn query(dir: &Path) -> Result<bool, String> {
+    let path = dir.join(".socket.sock");
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| format!("Failed to connect to {}: {}", path.display(), err))?;
+    let mut reply = String::new();
+    stream
+        .write_all(b"j/activeworkspace")
+        .and_then(|_| stream.read_to_string(&mut reply))
+        .map_err(|err| format!("Failed to query Hyprland: {}", err))?;
+    let workspace: Value = serde_json::from_str(&reply)
+        .map_err(|err| format!("Invalid reply from Hyprland: {}", err))?;
+    Ok(workspace["hasfullscreen"] == Value::Bool(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixListener, thread};
+
+    use super::*;
+
+    /// Answer each query with a workspace in turn,
+    /// sending the event before each but the first.
+    fn serve(dir: PathBuf, workspaces: Vec<(&'static str, &'static str)>) {
+        let events = UnixListener::bind(dir.join(".socket2.sock")).unwrap();
+        let queries = UnixListener::bind(dir.join(".socket.sock")).unwrap();
+        thread::spawn(move || {
+            let (mut events, _) = events.accept().expect("Should accept events");
+            for (event, workspace) in workspaces {
+                if !event.is_empty() {
+                    writeln!(events, "{}>>1", event).unwrap();
+                }
+                let (mut query, _) = queries.accept().expect("Should accept a query");
+                let mut request = [0; 17];
+                query.read_exact(&mut request).unwrap();
+                assert_eq!(&request, b"j/activeworkspace");
+                query.write_all(workspace.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    /// The states reported for each workspace, until the fake Hyprland goes away.
+    fn states(workspaces: Vec<(&'static str, &'static str)>) -> Vec<Result<bool, String>> {
+        let dir = tempfile::tempdir().unwrap();
+        serve(dir.path().to_path_buf(), workspaces);
+        let mut states = vec![];
+        let result = subscribe(dir.path(), &mut |state| states.push(state));
+        assert!(result.is_err(), "Should end with the connection");
+        states
+    }
+
+    const FULLSCREEN: &str = r#"{"id": 1, "name": "1", "hasfullscreen": true}"#;
+    const TILED: &str = r#"{"id": 1, "name": "1", "hasfullscreen": false}"#;
+
+    #[test]
+    fn detects_fullscreen_workspace() {
+        assert_eq!(states(vec![("", FULLSCREEN)]), vec![Ok(true)]);
+        assert_eq!(states(vec![("", TILED)]), vec![Ok(false)]);
+    }
+
+    #[test]
+    fn follows_events() {
+        let workspaces = vec![
+            ("", TILED),
+            ("fullscreen", FULLSCREEN),
+            ("workspace", TILED),
+        ];
+        assert_eq!(states(workspaces), vec![Ok(false), Ok(true), Ok(false)]);
+    }
+
+    #[test]
+    fn reports_invalid_replies() {
+        let states = states(vec![("", "unknown request")]);
+        assert!(matches!(states[..], [Err(_)]));
+    }
+}
@@ -0,0 +1,84 @@
+//! Layout names from a river status client, since river only
+//! reports them over a Wayland protocol.
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+};
+
+use serde_json::Value;
+
+use super::Options;
+use crate::status::Update;
+
+/// Run the status command, reporting whether the layout is monocle
+/// every time it prints one. Lines are either a layout name or a
+/// JSON object with a `layout` field, like `ristate --layout` prints.
+/// Blocks until the command exits.
+pub fn subscribe(options: &Options, update: Update<bool>) -> Result<(), String> {
+    let Some((program, args)) = options.river_command.split_first() else {
+        return Err("No river_command configured".into());
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to execute {}: {}", program, err))?;
+    let stdout = child.stdout.take().expect("Stdout should be piped");
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if let Some(layout) = layout(&line) {
+            update(Ok(options.is_monocle(&layout)));
+        }
+    }
+
+    let status = child.wait().map_err(|err| err.to_string())?;
+    Err(format!("{} exited with {}", program, status))
+}
+
+fn layout(line: &str) -> Option<String> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(status)) => status.get("layout")?.as_str().map(String::from),
+        _ => Some(line.trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::layout::Backend;
+
+    #[test]
+    fn reads_plain_names() {
+        assert_eq!(layout("monocle"), Some("monocle".into()));
+        assert_eq!(layout("[]=\n"), Some("[]=".into()));
+    }
+
+    #[test]
+    fn reads_json() {
+        assert_eq!(
+            layout(r#"{"layout":"rivertile - left"}"#),
+            Some("rivertile - left".into())
+        );
+        // Other status, e.g. tags.
+        assert_eq!(layout(r#"{"tags":{"DP-1":["1"]}}"#), None);
+        assert_eq!(layout(r#"{"layout":null}"#), None);
+    }
+
+    #[test]
+    fn follows_command() {
+        let options = Options {
+            backend: Backend::River,
+            river_command: ["printf", "monocle\\n[]=\\n"].map(String::from).to_vec(),
+            ..Options::default()
+        };
+        let mut states = vec![];
+        let result = subscribe(&options, &mut |state| {
+            states.push(state.expect("State should be known"))
+        });
+        assert!(result.is_err(), "Should end with the command");
+        assert_eq!(states, vec![true, false]);
+    }
+}
@@ -0,0 +1,246 @@
+//! Fullscreen and tabbed/stacked containers from the sway or i3 IPC.
+
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use super::{Backend, Options};
+use crate::status::{cmd, Update};
+
+const MAGIC: &[u8] = b"i3-ipc";
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+
+/// Set on the type of messages which are events rather than replies.
+const EVENT: u32 = 1 << 31;
+
+/// Events after which the layout may have changed. Layout commands
+/// don't have their own, but are usually triggered by a binding.
+const EVENTS: &str = r#"["window", "workspace", "binding"]"#;
+
+/// Report whether the focused window is fullscreen (or in a monocle
+/// container), and again after every relevant event.
+/// Blocks until the connection is lost.
+pub fn subscribe(path: &Path, options: &Options, update: Update<bool>) -> Result<(), String> {
+    let connect = || {
+        UnixStream::connect(path)
+            .map_err(|err| format!("Failed to connect to {}: {}", path.display(), err))
+    };
+    let mut events = connect()?;
+    let mut queries = connect()?;
+
+    send(&mut events, SUBSCRIBE, EVENTS)?;
+    let (_, reply) = receive(&mut events)?;
+    if reply["success"] != Value::Bool(true) {
+        return Err(format!("Failed to subscribe to events: {}", reply));
+    }
+
+    update(Ok(query(&mut queries, options)?));
+    loop {
+        let (kind, _) = receive(&mut events)?;
+        if kind & EVENT != 0 {
+            update(Ok(query(&mut queries, options)?));
+        }
+    }
+}
+
+/// Location of the IPC socket.
+pub fn socket(backend: Backend) -> Result<PathBuf, String> {
+    let path = if backend == Backend::I3 {
+        env::var("I3SOCK").or_else(|_| cmd("i3", &["--get-socketpath"]))
+    } else {
+        env::var("SWAYSOCK").map_err(|_| "SWAYSOCK is not set".to_string())
+    };
+    path.map(PathBuf::from)
+}
+
+fn query(stream: &mut UnixStream, options: &Options) -> Result<bool, String> {
+    send(stream, GET_TREE, "")?;
+    let (_, tree) = receive(stream)?;
+    let Some(path) = focused(&tree) else {
+        return Ok(false);
+    };
+    let fullscreen = path
+        .iter()
+        .any(|node| node["fullscreen_mode"].as_u64().unwrap_or(0) != 0);
+    let monocle = path
+        .iter()
+        .rev()
+        .nth(1)
+        .and_then(|parent| parent["layout"].as_str())
+        .is_some_and(|layout| options.is_monocle(layout));
+    Ok(fullscreen || monocle)
+}
+
+/// The nodes from the root down to the focused one.
+fn focused(node: &Value) -> Option<Vec<&Value>> {
+    if node["focused"] == Value::Bool(true) {
+        return Some(vec![node]);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(|child| {
+            let mut path = focused(child)?;
+            path.insert(0, node);
+            Some(path)
+        })
+}
+
+fn send(stream: &mut UnixStream, kind: u32, payload: &str) -> Result<(), String> {
+    let mut msg = MAGIC.to_vec();
+    msg.extend((payload.len() as u32).to_ne_bytes());
+    msg.extend(kind.to_ne_bytes());
+    msg.extend(payload.as_bytes());
+    stream
+        .write_all(&msg)
+        .map_err(|err| format!("Failed to send IPC message: {}", err))
+}
+
+/// Read the next message, returning its type and payload.
+fn receive(stream: &mut UnixStream) -> Result<(u32, Value), String> {
+    let err = |err: std::io::Error| format!("Failed to read IPC message: {}", err);
+    let mut header = [0; 14];
+    stream.read_exact(&mut header).map_err(err)?;
+    if &header[..6] != MAGIC {
+        return Err("Invalid IPC message".into());
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().expect("Should be 4 bytes"));
+    let kind = u32::from_ne_bytes(header[10..14].try_into().expect("Should be 4 bytes"));
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload).map_err(err)?;
+    let payload =
+        serde_json::from_slice(&payload).map_err(|err| format!("Invalid IPC message: {}", err))?;
+    Ok((kind, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixListener, thread};
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// A tree with a workspace in the given layout, holding the
+    /// focused window (fullscreen or not) and another one.
+    fn tree(layout: &str, fullscreen: bool) -> Value {
+        json!({
+            "type": "root",
+            "layout": "splith",
+            "nodes": [{
+                "type": "output",
+                "layout": "output",
+                "nodes": [{
+                    "type": "workspace",
+                    "layout": layout,
+                    "nodes": [
+                        { "type": "con", "focused": false, "nodes": [] },
+                        {
+                            "type": "con",
+                            "focused": true,
+                            "fullscreen_mode": if fullscreen { 1 } else { 0 },
+                            "nodes": [],
+                        },
+                    ],
+                    "floating_nodes": [],
+                }],
+            }],
+        })
+    }
+
+    /// Read the next message, returning its type.
+    fn read_kind(stream: &mut UnixStream) -> u32 {
+        let mut header = [0; 14];
+        stream
+            .read_exact(&mut header)
+            .expect("Should read a header");
+        let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+        stream
+            .read_exact(&mut vec![0; len as usize])
+            .expect("Should read a payload");
+        u32::from_ne_bytes(header[10..14].try_into().unwrap())
+    }
+
+    /// Serve each tree in turn, sending an event before each but the first.
+    fn serve(listener: UnixListener, trees: Vec<Value>) {
+        let (mut events, _) = listener.accept().expect("Should accept events");
+        let (mut queries, _) = listener.accept().expect("Should accept queries");
+        assert_eq!(read_kind(&mut events), SUBSCRIBE);
+        send(&mut events, SUBSCRIBE, r#"{"success":true}"#).unwrap();
+        for (i, tree) in trees.iter().enumerate() {
+            if i > 0 {
+                send(&mut events, EVENT, r#"{"change":"fullscreen_mode"}"#).unwrap();
+            }
+            assert_eq!(read_kind(&mut queries), GET_TREE);
+            send(&mut queries, GET_TREE, &tree.to_string()).unwrap();
+        }
+    }
+
+    /// The states reported for each tree, until the fake sway goes away.
+    fn states(trees: Vec<Value>) -> Vec<bool> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sway.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = thread::spawn(move || serve(listener, trees));
+
+        let options = Options {
+            backend: Backend::Sway,
+            ..Options::default()
+        };
+        let mut states = vec![];
+        let result = subscribe(&path, &options, &mut |state| {
+            states.push(state.expect("State should be known"))
+        });
+        assert!(result.is_err(), "Should end with the connection");
+        server.join().expect("Fake sway should not panic");
+        states
+    }
+
+    #[test]
+    fn detects_fullscreen_window() {
+        assert_eq!(states(vec![tree("splith", true)]), vec![true]);
+    }
+
+    #[test]
+    fn detects_monocle_containers() {
+        assert_eq!(states(vec![tree("tabbed", false)]), vec![true]);
+        assert_eq!(states(vec![tree("stacked", false)]), vec![true]);
+    }
+
+    #[test]
+    fn ignores_tiled_windows() {
+        assert_eq!(states(vec![tree("splitv", false)]), vec![false]);
+    }
+
+    #[test]
+    fn follows_events() {
+        let trees = vec![
+            tree("splith", false),
+            tree("splith", true),
+            tree("tabbed", false),
+            tree("splith", false),
+        ];
+        assert_eq!(states(trees), vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn finds_focused_floating_window() {
+        let tree = json!({
+            "nodes": [],
+            "floating_nodes": [{
+                "layout": "tabbed",
+                "nodes": [{ "focused": true, "nodes": [] }],
+            }],
+        });
+        let path = focused(&tree).expect("Should find the focused window");
+        assert_eq!(path.len(), 3);
+        assert_eq!(path[1]["layout"], "tabbed");
+    }
+}
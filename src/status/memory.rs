@@ -0,0 +1,222 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use super::{
+    ramp::{Direction, Ramp},
+    Bar, Indicator, Overlay,
+};
+use crate::config::Colors;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Usage at or above which the bar turns to a warning.
+    warn: f64,
+
+    /// Usage at or above which the bar turns urgent.
+    urgent: f64,
+
+    /// Also turn urgent when tasks stall waiting for memory,
+    /// according to `/proc/pressure/memory`.
+    pressure: bool,
+
+    /// Percent of the last 10 seconds some task stalled
+    /// at or above which the bar turns urgent.
+    pressure_threshold: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            warn: 0.8,
+            urgent: 0.95,
+            pressure: false,
+            pressure_threshold: 10.,
+        }
+    }
+}
+
+/// Memory state, with usage from 0 to 1.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    memory: f64,
+
+    /// Total memory, in kB.
+    total: u64,
+
+    /// `None` without any swap.
+    swap: Option<f64>,
+
+    /// Stall percentage over the last 10 seconds.
+    pressure: Option<f64>,
+}
+
+/// A bar representing the memory usage, with swap usage over it.
+pub struct Memory {
+    colors: Colors,
+    options: Options,
+    levels: Ramp,
+    usage: Option<Usage>,
+}
+
+impl Memory {
+    pub fn new(colors: Colors, options: Options) -> Self {
+        let levels = Ramp::new(
+            vec![(options.warn, colors.warn), (options.urgent, colors.urgent)],
+            Direction::Rising,
+            false,
+        );
+        Memory {
+            colors,
+            options,
+            levels,
+            usage: None,
+        }
+    }
+}
+
+impl Indicator for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let (memory, total, swap) = meminfo(&read("/proc/meminfo")?)?;
+        let pressure = if self.options.pressure {
+            Some(pressure(&read("/proc/pressure/memory")?)?)
+        } else {
+            None
+        };
+        self.usage = Some(Usage {
+            memory,
+            total,
+            swap,
+            pressure,
+        });
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let usage = self.usage?;
+        let stalling = usage
+            .pressure
+            .is_some_and(|pressure| pressure >= self.options.pressure_threshold);
+        let color = if stalling {
+            self.colors.urgent
+        } else {
+            self.levels.color(usage.memory, self.colors.normal)
+        };
+        Some((usage.memory, color))
+    }
+
+    fn overlays(&self) -> Vec<Overlay> {
+        match self.usage.and_then(|usage| usage.swap) {
+            Some(swap) if swap > 0. => {
+                let [r, g, b, a] = self.colors.mute;
+                vec![(0., (swap, [r, g, b, a * 0.5]))]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn details(&self) -> Option<String> {
+        let usage = self.usage?;
+        let gib = usage.total as f64 / (1024. * 1024.);
+        let mut details = format!("Memory {:.0}% of {:.1} GiB", usage.memory * 100., gib);
+        if let Some(swap) = usage.swap {
+            details += &format!("\nSwap {:.0}%", swap * 100.);
+        }
+        if let Some(pressure) = usage.pressure {
+            details += &format!("\nStalled {:.1}% of the last 10s", pressure);
+        }
+        Some(details)
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path, err))
+}
+
+/// The memory usage, total memory and swap usage from `/proc/meminfo`.
+fn meminfo(meminfo: &str) -> Result<(f64, u64, Option<f64>), String> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+    };
+    let (Some(total), Some(available)) = (field("MemTotal"), field("MemAvailable")) else {
+        return Err("No memory totals in /proc/meminfo".into());
+    };
+    let memory = 1. - available as f64 / total as f64;
+    let swap = match (field("SwapTotal"), field("SwapFree")) {
+        (Some(total), Some(free)) if total > 0 => Some(1. - free as f64 / total as f64),
+        _ => None,
+    };
+    Ok((memory, total, swap))
+}
+
+/// Percent of the last 10 seconds some task stalled waiting
+/// for memory, from `/proc/pressure/memory`.
+fn pressure(pressure: &str) -> Result<f64, String> {
+    pressure
+        .lines()
+        .find_map(|line| line.strip_prefix("some "))
+        .and_then(|some| {
+            some.split_whitespace()
+                .find_map(|field| field.strip_prefix("avg10="))
+        })
+        .and_then(|avg| avg.parse().ok())
+        .ok_or("No stall average in /proc/pressure/memory".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_meminfo() {
+        let contents = "\
+MemTotal:       16000000 kB
+MemFree:         2000000 kB
+MemAvailable:    4000000 kB
+Buffers:          500000 kB
+SwapTotal:       8000000 kB
+SwapFree:        6000000 kB
+";
+        assert_eq!(meminfo(contents), Ok((0.75, 16000000, Some(0.25))));
+    }
+
+    #[test]
+    fn reads_meminfo_without_swap() {
+        let contents = "\
+MemTotal:        8000000 kB
+MemAvailable:    6000000 kB
+SwapTotal:             0 kB
+SwapFree:              0 kB
+";
+        assert_eq!(meminfo(contents), Ok((0.25, 8000000, None)));
+    }
+
+    #[test]
+    fn rejects_meminfo_without_totals() {
+        // Kernels before 3.14 don't have MemAvailable.
+        assert!(meminfo("MemTotal: 8000000 kB\nMemFree: 6000000 kB\n").is_err());
+    }
+
+    #[test]
+    fn reads_pressure() {
+        let contents = "\
+some avg10=12.50 avg60=3.00 avg300=1.00 total=123456
+full avg10=4.00 avg60=1.00 avg300=0.50 total=65432
+";
+        assert_eq!(pressure(contents), Ok(12.5));
+        assert!(pressure("full avg10=4.00 avg60=1.00\n").is_err());
+    }
+}
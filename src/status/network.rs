@@ -0,0 +1,727 @@
+mod netlink;
+mod nl80211;
+mod vpn;
+
+use std::{fs, net::IpAddr, time::Instant};
+
+use serde::Deserialize;
+
+use super::{Bar, Indicator, Overlay};
+use crate::config::Colors;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Where to look for an active VPN.
+    vpn: Vec<vpn::Provider>,
+
+    /// Interfaces to consider even though they're virtual.
+    /// A trailing `*` matches any suffix, e.g. `usb*`.
+    allow_interfaces: Vec<String>,
+
+    /// Interfaces to ignore even though they're physical.
+    deny_interfaces: Vec<String>,
+
+    /// Networks where no VPN is required.
+    trusted_ssids: Vec<String>,
+
+    /// Interfaces where no VPN is required, e.g. a home ethernet port.
+    trusted_interfaces: Vec<String>,
+
+    /// MAC addresses of gateways where no VPN is required.
+    trusted_gateways: Vec<String>,
+
+    /// Drivers of interfaces shared by a phone or modem.
+    tethered_drivers: Vec<String>,
+
+    /// Height of the marker showing the kind of connection,
+    /// as a percent of the segment. Zero to hide it.
+    marker_height: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            vpn: vec![vpn::Provider::Mullvad],
+            allow_interfaces: vec![],
+            deny_interfaces: vec![],
+            trusted_ssids: vec![],
+            trusted_interfaces: vec![],
+            trusted_gateways: vec![],
+            tethered_drivers: [
+                "rndis_host",
+                "ipheth",
+                "cdc_ether",
+                "cdc_ncm",
+                "cdc_mbim",
+                "qmi_wwan",
+            ]
+            .map(String::from)
+            .to_vec(),
+            marker_height: 0.25,
+        }
+    }
+}
+
+impl Options {
+    /// Whether we're on a network where no VPN is required. Only networks
+    /// carrying a default route count, so staying associated to a trusted
+    /// SSID while plugged into another network doesn't. `neighbour` looks
+    /// up the MAC address of a gateway.
+    fn trusts(
+        &self,
+        uplinks: &[(netlink::Route, &netlink::Link)],
+        wireless: &[nl80211::Wireless],
+        neighbour: impl Fn(i32, IpAddr) -> Result<Option<String>, String>,
+    ) -> Result<bool, String> {
+        let trusted_ssid = wireless
+            .iter()
+            .filter(|wireless| uplinks.iter().any(|(_, link)| link.index == wireless.index))
+            .filter_map(|wireless| wireless.ssid.as_ref())
+            .any(|ssid| self.trusted_ssids.contains(ssid));
+        let trusted_interface = uplinks
+            .iter()
+            .any(|(_, link)| matches(&self.trusted_interfaces, &link.name));
+        if trusted_ssid || trusted_interface {
+            return Ok(true);
+        }
+
+        if !self.trusted_gateways.is_empty() {
+            for (route, link) in uplinks {
+                let Some(gateway) = route.gateway else {
+                    continue;
+                };
+                if let Some(mac) = neighbour(link.index, gateway)? {
+                    if self
+                        .trusted_gateways
+                        .iter()
+                        .any(|trusted| trusted.eq_ignore_ascii_case(&mac))
+                    {
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// What kind of connection a link provides.
+    /// `driver` looks up the kernel driver of an interface.
+    fn medium(
+        &self,
+        link: &netlink::Link,
+        wireless: &[nl80211::Wireless],
+        driver: impl Fn(&str) -> Option<String>,
+    ) -> Medium {
+        if wireless.iter().any(|wireless| wireless.index == link.index) {
+            Medium::Wireless
+        } else if driver(&link.name).is_some_and(|driver| self.tethered_drivers.contains(&driver)) {
+            Medium::Tethered
+        } else {
+            Medium::Wired
+        }
+    }
+}
+
+/// The kind of connection carrying the default route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Medium {
+    Wired,
+    Wireless,
+    Tethered,
+}
+
+/// Name of the kernel driver behind an interface, if it has a device.
+fn driver(name: &str) -> Option<String> {
+    let path = fs::read_link(format!("/sys/class/net/{}/device/driver", name)).ok()?;
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Whether an interface name matches any of the patterns.
+/// A trailing `*` matches any suffix.
+fn matches(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+}
+
+/// Whether a link can provide connectivity, given the interfaces
+/// to consider even if virtual and to ignore even if physical.
+fn considers(allow: &[String], deny: &[String], link: &netlink::Link) -> bool {
+    if matches(allow, &link.name) {
+        true
+    } else if matches(deny, &link.name) {
+        false
+    } else {
+        !link.is_virtual
+    }
+}
+
+/// The default routes going through an interface which is up
+/// and considered. We're online if there's any.
+fn uplinks<'a>(
+    links: &'a [netlink::Link],
+    allow: &[String],
+    deny: &[String],
+) -> Result<Vec<(netlink::Route, &'a netlink::Link)>, String> {
+    let routes = netlink::default_routes()?;
+    Ok(routes
+        .into_iter()
+        .filter_map(|route| {
+            let link = links
+                .iter()
+                .find(|link| link.index == route.oif && link.up && considers(allow, deny, link))?;
+            Some((route, link))
+        })
+        .collect())
+}
+
+/// The link carrying the preferred default route, the one with the lowest metric.
+fn primary<'a>(uplinks: &[(netlink::Route, &'a netlink::Link)]) -> Option<&'a netlink::Link> {
+    uplinks
+        .iter()
+        .min_by_key(|(route, _)| route.priority)
+        .map(|(_, link)| *link)
+}
+
+/// A color representing the wifi/vpn state, with a marker
+/// for wireless or tethered connections.
+pub struct Wifi {
+    colors: Colors,
+    options: Options,
+    vpn: vpn::Vpn,
+    bar: Option<Bar>,
+    medium: Option<Medium>,
+}
+
+impl Wifi {
+    pub fn new(colors: Colors, options: Options) -> Self {
+        Wifi {
+            colors,
+            vpn: vpn::Vpn::new(options.vpn.clone()),
+            options,
+            bar: None,
+            medium: None,
+        }
+    }
+}
+
+impl Indicator for Wifi {
+    fn name(&self) -> &'static str {
+        "wifi"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        // Don't keep showing the last state if it can't be told anymore.
+        self.bar = None;
+        self.medium = None;
+        let links = netlink::links()?;
+        let uplinks = uplinks(
+            &links,
+            &self.options.allow_interfaces,
+            &self.options.deny_interfaces,
+        )?;
+        let color = if uplinks.is_empty() {
+            self.colors.bg
+        } else {
+            let wireless = nl80211::interfaces()?;
+            self.medium =
+                primary(&uplinks).map(|link| self.options.medium(link, &wireless, driver));
+            if self.vpn.connected(&links) {
+                self.colors.ok
+            } else if self
+                .options
+                .trusts(&uplinks, &wireless, netlink::neighbour_mac)?
+            {
+                self.colors.normal
+            } else if self.medium == Some(Medium::Wireless) {
+                self.colors.urgent
+            } else {
+                self.colors.mute
+            }
+        };
+        self.bar = Some((1.0, color));
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        self.bar
+    }
+
+    fn overlays(&self) -> Vec<Overlay> {
+        let height = self.options.marker_height;
+        let color = match self.medium {
+            Some(Medium::Wireless) => self.colors.mute,
+            Some(Medium::Tethered) => self.colors.warn,
+            Some(Medium::Wired) | None => return vec![],
+        };
+        if height <= 0. {
+            return vec![];
+        }
+        vec![(1. - height, (height, color))]
+    }
+
+    fn details(&self) -> Option<String> {
+        let unavailable = self.vpn.unavailable();
+        (!unavailable.is_empty()).then(|| format!("VPN not checked:\n{}", unavailable.join("\n")))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignalOptions {
+    /// Quality at or below which the bar turns to a warning.
+    threshold: f64,
+}
+
+impl Default for SignalOptions {
+    fn default() -> Self {
+        SignalOptions { threshold: 0.3 }
+    }
+}
+
+/// A bar representing the wifi signal quality,
+/// hidden when not connected to a wireless network.
+pub struct WifiSignal {
+    colors: Colors,
+    options: SignalOptions,
+    bar: Option<Bar>,
+}
+
+impl WifiSignal {
+    pub fn new(colors: Colors, options: SignalOptions) -> Self {
+        WifiSignal {
+            colors,
+            options,
+            bar: None,
+        }
+    }
+}
+
+impl Indicator for WifiSignal {
+    fn name(&self) -> &'static str {
+        "wifi_signal"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let quality = nl80211::interfaces()?
+            .iter()
+            .filter_map(|wireless| wireless.quality())
+            .reduce(f64::max);
+        self.bar = quality.map(|quality| {
+            let color = if quality <= self.options.threshold {
+                self.colors.warn
+            } else {
+                self.colors.normal
+            };
+            (quality, color)
+        });
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        self.bar
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThroughputOptions {
+    /// Interface to show, rather than the one with the default route.
+    interface: Option<String>,
+
+    /// Interfaces to consider for the default route even though they're
+    /// virtual, and to ignore even though they're physical, as for `wifi`.
+    allow_interfaces: Vec<String>,
+    deny_interfaces: Vec<String>,
+
+    /// Rate which fills a half of the segment, in Mbit/s.
+    max_mbps: f64,
+
+    /// Scale rates logarithmically, so light traffic is still visible.
+    log_scale: bool,
+}
+
+impl Default for ThroughputOptions {
+    fn default() -> Self {
+        ThroughputOptions {
+            interface: None,
+            allow_interfaces: vec![],
+            deny_interfaces: vec![],
+            max_mbps: 100.,
+            log_scale: false,
+        }
+    }
+}
+
+/// Byte counters of an interface at some point.
+#[derive(Debug, Clone)]
+struct Counters {
+    interface: String,
+    at: Instant,
+    rx: u64,
+    tx: u64,
+}
+
+/// Receive and transmit rates over the
+/// default route, in the lower and upper half.
+pub struct Throughput {
+    colors: Colors,
+    options: ThroughputOptions,
+    counters: Option<Counters>,
+
+    /// Bytes per second received and transmitted.
+    rates: Option<(String, f64, f64)>,
+}
+
+impl Throughput {
+    pub fn new(colors: Colors, options: ThroughputOptions) -> Self {
+        Throughput {
+            colors,
+            options,
+            counters: None,
+            rates: None,
+        }
+    }
+
+    /// A rate as a percent of the maximum.
+    fn scale(&self, rate: f64) -> f64 {
+        let max = self.options.max_mbps * 1e6 / 8.;
+        let percent = if self.options.log_scale {
+            (1. + rate).ln() / (1. + max).ln()
+        } else {
+            rate / max
+        };
+        percent.clamp(0., 1.)
+    }
+
+    /// Work out the rates since the last counters.
+    fn measure(&mut self, now: Counters) {
+        // Counters of another interface can't be compared.
+        self.rates = self
+            .counters
+            .as_ref()
+            .filter(|last| last.interface == now.interface)
+            .map(|last| {
+                let seconds = now.at.duration_since(last.at).as_secs_f64().max(0.001);
+                let rate = |now: u64, last: u64| now.saturating_sub(last) as f64 / seconds;
+                (
+                    now.interface.clone(),
+                    rate(now.rx, last.rx),
+                    rate(now.tx, last.tx),
+                )
+            });
+        self.counters = Some(now);
+    }
+}
+
+impl Indicator for Throughput {
+    fn name(&self) -> &'static str {
+        "throughput"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let interface = match &self.options.interface {
+            Some(interface) => Some(interface.clone()),
+            None => {
+                let links = netlink::links()?;
+                let uplinks = uplinks(
+                    &links,
+                    &self.options.allow_interfaces,
+                    &self.options.deny_interfaces,
+                )?;
+                primary(&uplinks).map(|link| link.name.clone())
+            }
+        };
+        let Some(interface) = interface else {
+            self.counters = None;
+            self.rates = None;
+            return Ok(());
+        };
+
+        let dev = fs::read_to_string("/proc/net/dev")
+            .map_err(|err| format!("Failed to read /proc/net/dev: {}", err))?;
+        let (rx, tx) = counters(&dev, &interface)?;
+        self.measure(Counters {
+            interface,
+            at: Instant::now(),
+            rx,
+            tx,
+        });
+        Ok(())
+    }
+
+    fn value(&self) -> Option<Bar> {
+        None
+    }
+
+    fn overlays(&self) -> Vec<Overlay> {
+        let Some((_, rx, tx)) = &self.rates else {
+            return vec![];
+        };
+        vec![
+            (0., (self.scale(*rx) / 2., self.colors.normal)),
+            (0.5, (self.scale(*tx) / 2., self.colors.ok)),
+        ]
+    }
+
+    fn details(&self) -> Option<String> {
+        let (interface, rx, tx) = self.rates.as_ref()?;
+        let mbps = |rate: f64| rate * 8. / 1e6;
+        Some(format!(
+            "{} down {:.2} Mbit/s, up {:.2} Mbit/s",
+            interface,
+            mbps(*rx),
+            mbps(*tx)
+        ))
+    }
+}
+
+/// Bytes received and transmitted by an interface since it came up,
+/// from `/proc/net/dev`.
+fn counters(dev: &str, interface: &str) -> Result<(u64, u64), String> {
+    let line = dev
+        .lines()
+        .find_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            (name.trim() == interface).then_some(counters)
+        })
+        .ok_or_else(|| format!("No counters for {}", interface))?;
+    // Receive bytes come first, then 7 more receive counters
+    // before the transmit bytes.
+    let fields: Vec<u64> = line
+        .split_whitespace()
+        .filter_map(|field| field.parse().ok())
+        .collect();
+    match (fields.first(), fields.get(8)) {
+        (Some(rx), Some(tx)) => Ok((*rx, *tx)),
+        _ => Err(format!("Invalid counters for {}", interface)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn link(index: i32, name: &str) -> netlink::Link {
+        netlink::Link {
+            index,
+            name: name.into(),
+            up: true,
+            is_virtual: false,
+            kind: None,
+        }
+    }
+
+    fn route(oif: i32, gateway: &str) -> netlink::Route {
+        netlink::Route {
+            oif,
+            gateway: Some(gateway.parse().unwrap()),
+            priority: 100,
+        }
+    }
+
+    fn wireless(index: i32, ssid: &str) -> nl80211::Wireless {
+        nl80211::Wireless {
+            index,
+            ssid: Some(ssid.into()),
+            signal: None,
+        }
+    }
+
+    /// Gateways and their MAC addresses, by interface.
+    fn neighbours<'a>(
+        known: &'a [(i32, &str, &str)],
+    ) -> impl Fn(i32, IpAddr) -> Result<Option<String>, String> + 'a {
+        move |index, addr| {
+            Ok(known
+                .iter()
+                .find(|(i, gateway, _)| *i == index && gateway.parse() == Ok(addr))
+                .map(|(_, _, mac)| mac.to_string()))
+        }
+    }
+
+    const DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:    1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0
+wlp3s0: 123456789  98765    0    2    0     0          0        11 23456789   54321    0    0    0     0       0          0
+";
+
+    #[test]
+    fn reads_counters() {
+        assert_eq!(counters(DEV, "wlp3s0"), Ok((123456789, 23456789)));
+        assert_eq!(counters(DEV, "lo"), Ok((1000, 1000)));
+        assert!(counters(DEV, "eth0").is_err());
+        assert!(counters("eth0: 1 2 3\n", "eth0").is_err());
+    }
+
+    fn throughput(options: &str) -> Throughput {
+        let options = toml::from_str(options).expect("Options should be valid");
+        Throughput::new(Colors::default(), options)
+    }
+
+    #[test]
+    fn scales_linearly() {
+        let throughput = throughput("max_mbps = 80");
+        assert_eq!(throughput.scale(0.), 0.);
+        assert_eq!(throughput.scale(5e6), 0.5);
+        assert_eq!(throughput.scale(20e6), 1.);
+    }
+
+    #[test]
+    fn scales_logarithmically() {
+        let throughput = throughput("max_mbps = 80\nlog_scale = true");
+        assert_eq!(throughput.scale(0.), 0.);
+        assert_eq!(throughput.scale(10e6), 1.);
+        // Light traffic still shows.
+        let light = throughput.scale(1e3);
+        assert!(light > 0.4 && light < 0.5, "{}", light);
+    }
+
+    #[test]
+    fn measures_rates_on_the_same_interface() {
+        let mut throughput = throughput("");
+        let start = Instant::now();
+        let counters = |interface: &str, seconds, rx, tx| Counters {
+            interface: interface.into(),
+            at: start + Duration::from_secs(seconds),
+            rx,
+            tx,
+        };
+        throughput.measure(counters("eth0", 0, 1000, 500));
+        assert_eq!(throughput.rates, None);
+
+        throughput.measure(counters("eth0", 2, 5000, 700));
+        assert_eq!(throughput.rates, Some(("eth0".into(), 2000., 100.)));
+
+        // Switched to wifi, whose counters can't be compared.
+        throughput.measure(counters("wlan0", 4, 100, 100));
+        assert_eq!(throughput.rates, None);
+        throughput.measure(counters("wlan0", 5, 1100, 100));
+        assert_eq!(throughput.rates, Some(("wlan0".into(), 1000., 0.)));
+    }
+
+    #[test]
+    fn matches_patterns() {
+        let patterns = ["eth0".into(), "usb*".into()];
+        assert!(matches(&patterns, "eth0"));
+        assert!(!matches(&patterns, "eth1"));
+        assert!(matches(&patterns, "usb0"));
+        assert!(matches(&patterns, "usb"));
+        assert!(!matches(&patterns, "wlan0"));
+        assert!(!matches(&[], "eth0"));
+    }
+
+    #[test]
+    fn considers_physical_links() {
+        let eth = link(2, "eth0");
+        let tun = netlink::Link {
+            is_virtual: true,
+            ..link(5, "tun0")
+        };
+        assert!(considers(&[], &[], &eth));
+        assert!(!considers(&[], &[], &tun));
+        assert!(considers(&["tun*".into()], &[], &tun));
+        assert!(!considers(&[], &["eth*".into()], &eth));
+        // Allowing wins over denying.
+        assert!(considers(&["eth0".into()], &["eth*".into()], &eth));
+    }
+
+    #[test]
+    fn tells_medium() {
+        let options = Options::default();
+        let driver = |name: &str| match name {
+            "usb0" => Some("rndis_host".into()),
+            "eth0" => Some("e1000e".into()),
+            _ => None,
+        };
+        let wifi = [wireless(3, "home")];
+        let medium = |link| options.medium(&link, &wifi, driver);
+        assert_eq!(medium(link(2, "eth0")), Medium::Wired);
+        assert_eq!(medium(link(3, "wlan0")), Medium::Wireless);
+        assert_eq!(medium(link(4, "usb0")), Medium::Tethered);
+        // No device behind it.
+        assert_eq!(medium(link(6, "ppp0")), Medium::Wired);
+    }
+
+    #[test]
+    fn trusts_ssid_of_uplink() {
+        let options = Options {
+            trusted_ssids: vec!["home".into()],
+            ..Options::default()
+        };
+        let wlan = link(3, "wlan0");
+        let eth = link(2, "eth0");
+        let uplinks = [(route(3, "192.168.1.1"), &wlan)];
+        assert_eq!(
+            options.trusts(&uplinks, &[wireless(3, "home")], neighbours(&[])),
+            Ok(true)
+        );
+        assert_eq!(
+            options.trusts(&uplinks, &[wireless(3, "cafe")], neighbours(&[])),
+            Ok(false)
+        );
+
+        // Still associated at home, but plugged into another network.
+        let uplinks = [(route(2, "10.0.0.1"), &eth)];
+        assert_eq!(
+            options.trusts(&uplinks, &[wireless(3, "home")], neighbours(&[])),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn trusts_interface() {
+        let options = Options {
+            trusted_interfaces: vec!["enp*".into()],
+            ..Options::default()
+        };
+        let dock = link(2, "enp0s31f6");
+        let usb = link(4, "usb0");
+        assert_eq!(
+            options.trusts(&[(route(2, "10.0.0.1"), &dock)], &[], neighbours(&[])),
+            Ok(true)
+        );
+        assert_eq!(
+            options.trusts(&[(route(4, "10.0.0.1"), &usb)], &[], neighbours(&[])),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn trusts_gateway() {
+        let options = Options {
+            trusted_gateways: vec!["AA:BB:CC:00:11:22".into()],
+            ..Options::default()
+        };
+        let eth = link(2, "eth0");
+        let wlan = link(3, "wlan0");
+        let known = [
+            (2, "10.0.0.1", "aa:bb:cc:00:11:22"),
+            (3, "192.168.1.1", "de:ad:be:ef:00:01"),
+        ];
+        assert_eq!(
+            options.trusts(&[(route(2, "10.0.0.1"), &eth)], &[], neighbours(&known)),
+            Ok(true)
+        );
+        assert_eq!(
+            options.trusts(
+                &[(route(3, "192.168.1.1"), &wlan)],
+                &[wireless(3, "cafe")],
+                neighbours(&known)
+            ),
+            Ok(false)
+        );
+        // The gateway's address isn't known yet.
+        assert_eq!(
+            options.trusts(&[(route(2, "10.0.0.2"), &eth)], &[], neighbours(&known)),
+            Ok(false)
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Link and route state from rtnetlink.
+
+use std::net::IpAddr;
+
+use neli::{
+    consts::{
+        nl::{NlmF, NlmFFlags},
+        rtnl::{
+            Arphrd, Iff, IffFlags, Ifla, IflaInfo, Nda, NtfFlags, NudFlags, RtAddrFamily, RtScope,
+            RtTable, Rta, Rtm, RtmFFlags, Rtn, Rtprot,
+        },
+        socket::NlFamily,
+    },
+    nl::{NlPayload, Nlmsghdr},
+    rtnl::{Ifinfomsg, Ndmsg, Rtmsg},
+    socket::NlSocketHandle,
+    types::RtBuffer,
+};
+
+/// A network interface.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub index: i32,
+    pub name: String,
+
+    /// Administratively up and with a carrier.
+    pub up: bool,
+
+    /// Loopback, or a software device like a bridge, veth or tunnel.
+    pub is_virtual: bool,
+
+    /// Driver kind of software devices, e.g. `wireguard` or `tun`.
+    pub kind: Option<String>,
+}
+
+/// A default route in the main table.
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// Index of the outgoing interface.
+    pub oif: i32,
+    pub gateway: Option<IpAddr>,
+
+    /// Route metric, lower is preferred.
+    pub priority: u32,
+}
+
+fn connect() -> Result<NlSocketHandle, String> {
+    NlSocketHandle::connect(NlFamily::Route, None, &[])
+        .map_err(|err| format!("Failed to open netlink socket: {}", err))
+}
+
+fn dump_flags() -> NlmFFlags {
+    NlmFFlags::new(&[NlmF::Request, NlmF::Dump])
+}
+
+/// List all network interfaces.
+pub fn links() -> Result<Vec<Link>, String> {
+    let mut socket = connect()?;
+    let msg = Ifinfomsg::new(
+        RtAddrFamily::Unspecified,
+        Arphrd::None,
+        0,
+        IffFlags::empty(),
+        IffFlags::empty(),
+        RtBuffer::new(),
+    );
+    socket
+        .send(Nlmsghdr::new(
+            None,
+            Rtm::Getlink,
+            dump_flags(),
+            None,
+            None,
+            NlPayload::Payload(msg),
+        ))
+        .map_err(|err| err.to_string())?;
+
+    let mut links = vec![];
+    for msg in socket.iter::<Rtm, Ifinfomsg>(false) {
+        let msg = msg.map_err(|err| err.to_string())?;
+        let NlPayload::Payload(link) = msg.nl_payload else {
+            continue;
+        };
+        let mut attrs = link.rtattrs.get_attr_handle();
+        let Ok(name) = attrs.get_attr_payload_as_with_len::<String>(Ifla::Ifname) else {
+            continue;
+        };
+        // Software devices report their driver kind, physical ones don't.
+        let kind = attrs
+            .get_nested_attributes::<IflaInfo>(Ifla::Linkinfo)
+            .ok()
+            .and_then(|info| {
+                info.get_attr_payload_as_with_len::<String>(IflaInfo::Kind)
+                    .ok()
+            });
+        let flags = &link.ifi_flags;
+        links.push(Link {
+            index: link.ifi_index,
+            name,
+            up: flags.contains(&Iff::Up) && flags.contains(&Iff::Running),
+            is_virtual: flags.contains(&Iff::Loopback) || kind.is_some(),
+            kind,
+        });
+    }
+    Ok(links)
+}
+
+/// List the default routes of the main table, for both IPv4 and IPv6.
+pub fn default_routes() -> Result<Vec<Route>, String> {
+    let mut socket = connect()?;
+    let msg = Rtmsg {
+        rtm_family: RtAddrFamily::Unspecified,
+        rtm_dst_len: 0,
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: RtTable::Unspec,
+        rtm_protocol: Rtprot::Unspec,
+        rtm_scope: RtScope::Universe,
+        rtm_type: Rtn::Unspec,
+        rtm_flags: RtmFFlags::empty(),
+        rtattrs: RtBuffer::new(),
+    };
+    socket
+        .send(Nlmsghdr::new(
+            None,
+            Rtm::Getroute,
+            dump_flags(),
+            None,
+            None,
+            NlPayload::Payload(msg),
+        ))
+        .map_err(|err| err.to_string())?;
+
+    let mut routes = vec![];
+    for msg in socket.iter::<Rtm, Rtmsg>(false) {
+        let msg = msg.map_err(|err| err.to_string())?;
+        let NlPayload::Payload(route) = msg.nl_payload else {
+            continue;
+        };
+        if route.rtm_table != RtTable::Main
+            || route.rtm_type != Rtn::Unicast
+            || route.rtm_dst_len != 0
+        {
+            continue;
+        }
+        let attrs = route.rtattrs.get_attr_handle();
+        let priority = attrs.get_attr_payload_as::<u32>(Rta::Priority).unwrap_or(0);
+        if let Ok(oif) = attrs.get_attr_payload_as::<i32>(Rta::Oif) {
+            let gateway = attrs
+                .get_attribute(Rta::Gateway)
+                .and_then(|attr| to_addr(attr.rta_payload.as_ref()));
+            routes.push(Route {
+                oif,
+                gateway,
+                priority,
+            });
+        } else if let Some(multipath) = attrs.get_attribute(Rta::Multipath) {
+            // Multipath (ECMP) routes list their interfaces in their nexthops.
+            for (oif, gateway) in nexthops(multipath.rta_payload.as_ref()) {
+                routes.push(Route {
+                    oif,
+                    gateway,
+                    priority,
+                });
+            }
+        }
+    }
+    Ok(routes)
+}
+
+/// The outgoing interface and gateway of each nexthop of a multipath
+/// route, given as `struct rtnexthop`s each followed by its attributes.
+fn nexthops(mut bytes: &[u8]) -> Vec<(i32, Option<IpAddr>)> {
+    const RTNEXTHOP_LEN: usize = 8;
+    const RTATTR_LEN: usize = 4;
+    let align = |len: usize| (len + 3) & !3;
+    let u16_at = |bytes: &[u8], at: usize| u16::from_ne_bytes([bytes[at], bytes[at + 1]]) as usize;
+
+    let mut nexthops = vec![];
+    while bytes.len() >= RTNEXTHOP_LEN {
+        let len = u16_at(bytes, 0);
+        if len < RTNEXTHOP_LEN || len > bytes.len() {
+            break;
+        }
+        let oif = i32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let mut gateway = None;
+        let mut attrs = &bytes[RTNEXTHOP_LEN..len];
+        while attrs.len() >= RTATTR_LEN {
+            let attr_len = u16_at(attrs, 0);
+            if attr_len < RTATTR_LEN || attr_len > attrs.len() {
+                break;
+            }
+            if u16_at(attrs, 2) == u16::from(Rta::Gateway) as usize {
+                gateway = to_addr(&attrs[RTATTR_LEN..attr_len]);
+            }
+            attrs = &attrs[align(attr_len).min(attrs.len())..];
+        }
+        nexthops.push((oif, gateway));
+        bytes = &bytes[align(len).min(bytes.len())..];
+    }
+    nexthops
+}
+
+/// Look up the hardware address of a neighbour (e.g. the gateway)
+/// in the ARP/NDP cache, formatted like `aa:bb:cc:dd:ee:ff`.
+pub fn neighbour_mac(index: i32, addr: IpAddr) -> Result<Option<String>, String> {
+    let mut socket = connect()?;
+    let msg = Ndmsg::new(
+        RtAddrFamily::Unspecified,
+        0,
+        NudFlags::empty(),
+        NtfFlags::empty(),
+        Rtn::Unspec,
+        RtBuffer::new(),
+    );
+    socket
+        .send(Nlmsghdr::new(
+            None,
+            Rtm::Getneigh,
+            dump_flags(),
+            None,
+            None,
+            NlPayload::Payload(msg),
+        ))
+        .map_err(|err| err.to_string())?;
+
+    for msg in socket.iter::<Rtm, Ndmsg>(false) {
+        let msg = msg.map_err(|err| err.to_string())?;
+        let NlPayload::Payload(neighbour) = msg.nl_payload else {
+            continue;
+        };
+        if neighbour.ndm_index != index {
+            continue;
+        }
+        let attrs = neighbour.rtattrs.get_attr_handle();
+        let dst = attrs
+            .get_attribute(Nda::Dst)
+            .and_then(|attr| to_addr(attr.rta_payload.as_ref()));
+        if dst != Some(addr) {
+            continue;
+        }
+        let mac = attrs.get_attribute(Nda::Lladdr).map(|attr| {
+            attr.rta_payload
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(":")
+        });
+        return Ok(mac);
+    }
+    Ok(None)
+}
+
+fn to_addr(bytes: &[u8]) -> Option<IpAddr> {
+    if let Ok(octets) = <[u8; 4]>::try_from(bytes) {
+        Some(IpAddr::from(octets))
+    } else if let Ok(octets) = <[u8; 16]>::try_from(bytes) {
+        Some(IpAddr::from(octets))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `struct rtnexthop` through an interface, with a gateway attribute.
+    fn nexthop(oif: i32, gateway: Option<[u8; 4]>) -> Vec<u8> {
+        let len: u16 = if gateway.is_some() { 16 } else { 8 };
+        let mut bytes = len.to_ne_bytes().to_vec();
+        bytes.extend([0, 0]);
+        bytes.extend(oif.to_ne_bytes());
+        if let Some(gateway) = gateway {
+            bytes.extend(8u16.to_ne_bytes());
+            bytes.extend(u16::from(Rta::Gateway).to_ne_bytes());
+            bytes.extend(gateway);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_multipath_nexthops() {
+        let bytes = [
+            nexthop(2, Some([192, 168, 1, 1])),
+            nexthop(3, None),
+            nexthop(4, Some([10, 0, 0, 1])),
+        ]
+        .concat();
+        assert_eq!(
+            nexthops(&bytes),
+            vec![
+                (2, Some(IpAddr::from([192, 168, 1, 1]))),
+                (3, None),
+                (4, Some(IpAddr::from([10, 0, 0, 1]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_truncated_nexthops() {
+        let mut bytes = nexthop(2, Some([192, 168, 1, 1]));
+        bytes.extend(&nexthop(3, None)[..6]);
+        assert_eq!(
+            nexthops(&bytes),
+            vec![(2, Some(IpAddr::from([192, 168, 1, 1])))]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Wireless interface state from nl80211.
+
+use neli::{
+    consts::{
+        genl::{Cmd, NlAttrType},
+        nl::{NlmF, NlmFFlags},
+        socket::NlFamily,
+    },
+    genl::{Genlmsghdr, Nlattr},
+    neli_enum,
+    nl::{NlPayload, Nlmsghdr},
+    socket::NlSocketHandle,
+    types::{Buffer, GenlBuffer},
+};
+
+#[neli_enum(serialized_type = "u8")]
+enum Command {
+    Unspec = 0,
+    GetInterface = 5,
+    GetStation = 17,
+}
+impl Cmd for Command {}
+
+#[neli_enum(serialized_type = "u16")]
+enum Attr {
+    Unspec = 0,
+    Ifindex = 3,
+    StaInfo = 21,
+    Ssid = 52,
+}
+impl NlAttrType for Attr {}
+
+#[neli_enum(serialized_type = "u16")]
+enum StaInfo {
+    Unspec = 0,
+    Signal = 7,
+}
+impl NlAttrType for StaInfo {}
+
+/// A wireless interface.
+#[derive(Debug, Clone)]
+pub struct Wireless {
+    pub index: i32,
+
+    /// The network the interface is connected to, if any.
+    pub ssid: Option<String>,
+
+    /// Signal strength of the access point, in dBm.
+    pub signal: Option<i8>,
+}
+
+impl Wireless {
+    /// Signal quality from 0 to 1, mapped the same way as NetworkManager.
+    pub fn quality(&self) -> Option<f64> {
+        let dbm = self.signal? as f64;
+        Some((2. * (dbm + 100.) / 100.).clamp(0., 1.))
+    }
+}
+
+type Message = Genlmsghdr<Command, Attr>;
+
+/// List the wireless interfaces.
+/// Machines without any wireless support simply have none.
+pub fn interfaces() -> Result<Vec<Wireless>, String> {
+    let mut socket = NlSocketHandle::connect(NlFamily::Generic, None, &[])
+        .map_err(|err| format!("Failed to open netlink socket: {}", err))?;
+    let Ok(family) = socket.resolve_genl_family("nl80211") else {
+        return Ok(vec![]);
+    };
+
+    let mut interfaces = vec![];
+    for msg in dump(
+        &mut socket,
+        family,
+        Command::GetInterface,
+        GenlBuffer::new(),
+    )? {
+        let attrs = msg.get_attr_handle();
+        let Ok(index) = attrs.get_attr_payload_as::<u32>(Attr::Ifindex) else {
+            continue;
+        };
+        let ssid = attrs
+            .get_attr_payload_as_with_len::<&[u8]>(Attr::Ssid)
+            .ok()
+            .map(|ssid| String::from_utf8_lossy(ssid).into_owned());
+        interfaces.push(Wireless {
+            index: index as i32,
+            ssid,
+            signal: None,
+        });
+    }
+
+    for wireless in interfaces.iter_mut().filter(|w| w.ssid.is_some()) {
+        wireless.signal = signal(&mut socket, family, wireless.index);
+    }
+    Ok(interfaces)
+}
+
+/// Signal strength of the station an interface is associated with.
+fn signal(socket: &mut NlSocketHandle, family: u16, index: i32) -> Option<i8> {
+    let mut attrs = GenlBuffer::new();
+    attrs.push(Nlattr::new(false, false, Attr::Ifindex, index as u32).ok()?);
+    dump(socket, family, Command::GetStation, attrs)
+        .ok()?
+        .iter()
+        .find_map(|msg| {
+            let mut attrs = msg.get_attr_handle();
+            let info = attrs.get_nested_attributes::<StaInfo>(Attr::StaInfo).ok()?;
+            info.get_attr_payload_as::<u8>(StaInfo::Signal)
+                .ok()
+                .map(|signal| signal as i8)
+        })
+}
+
+fn dump(
+    socket: &mut NlSocketHandle,
+    family: u16,
+    cmd: Command,
+    attrs: GenlBuffer<Attr, Buffer>,
+) -> Result<Vec<Message>, String> {
+    socket
+        .send(Nlmsghdr::new(
+            None,
+            family,
+            NlmFFlags::new(&[NlmF::Request, NlmF::Dump]),
+            None,
+            None,
+            NlPayload::Payload(Genlmsghdr::new(cmd, 1, attrs)),
+        ))
+        .map_err(|err| err.to_string())?;
+
+    let mut msgs = vec![];
+    for msg in socket.iter::<u16, Message>(false) {
+        let msg = msg.map_err(|err| err.to_string())?;
+        if let NlPayload::Payload(payload) = msg.nl_payload {
+            msgs.push(payload);
+        }
+    }
+    Ok(msgs)
+}
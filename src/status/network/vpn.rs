@@ -0,0 +1,132 @@
+//! Detection of active VPN tunnels.
+
+use serde::Deserialize;
+use zbus::blocking::{Connection, Proxy};
+
+use super::netlink::Link;
+use crate::status::cmd;
+
+const NM: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// `NM_ACTIVE_CONNECTION_STATE_ACTIVATED`
+const NM_ACTIVATED: u32 = 2;
+
+/// A way of checking for an active VPN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    /// The `mullvad` CLI reports a connection.
+    Mullvad,
+
+    /// A WireGuard interface is up.
+    Wireguard,
+
+    /// A tun/tap device (as used by OpenVPN) is up.
+    Openvpn,
+
+    /// NetworkManager has an activated VPN or WireGuard connection.
+    NetworkManager,
+
+    /// The Tailscale backend is running.
+    Tailscale,
+}
+
+/// Checks the configured providers for an active VPN,
+/// keeping the system bus connection between refreshes.
+pub struct Vpn {
+    providers: Vec<Provider>,
+    system_bus: Option<Connection>,
+
+    /// Why providers couldn't be checked on the last refresh.
+    unavailable: Vec<String>,
+}
+
+impl Vpn {
+    pub fn new(providers: Vec<Provider>) -> Self {
+        Vpn {
+            providers,
+            system_bus: None,
+            unavailable: vec![],
+        }
+    }
+
+    /// Whether any of the providers has an active VPN. Providers which
+    /// can't be checked (e.g. not installed) count as no VPN, rather than
+    /// vouching for one which may be down, see `unavailable`.
+    pub fn connected(&mut self, links: &[Link]) -> bool {
+        self.unavailable.clear();
+        for provider in self.providers.clone() {
+            match self.check(provider, links) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(err) => self.unavailable.push(err),
+            }
+        }
+        false
+    }
+
+    /// Why providers couldn't be checked on the last refresh.
+    pub fn unavailable(&self) -> &[String] {
+        &self.unavailable
+    }
+
+    fn check(&mut self, provider: Provider, links: &[Link]) -> Result<bool, String> {
+        let link_up = |kind: &str| {
+            links
+                .iter()
+                .any(|link| link.up && link.kind.as_deref() == Some(kind))
+        };
+        match provider {
+            Provider::Mullvad => Ok(cmd("mullvad", &["status"])?.contains("Connected")),
+            Provider::Wireguard => Ok(link_up("wireguard")),
+            Provider::Openvpn => Ok(link_up("tun")),
+            Provider::NetworkManager => self
+                .network_manager()
+                .map_err(|err| format!("Failed to query NetworkManager: {}", err)),
+            Provider::Tailscale => tailscale(),
+        }
+    }
+
+    fn network_manager(&mut self) -> zbus::Result<bool> {
+        let conn = match &self.system_bus {
+            Some(conn) => conn,
+            None => self.system_bus.insert(Connection::system()?),
+        };
+        let result = network_manager(conn);
+        if result.is_err() {
+            // Reconnect on the next refresh, in case the bus went away.
+            self.system_bus = None;
+        }
+        result
+    }
+}
+
+fn network_manager(conn: &Connection) -> zbus::Result<bool> {
+    let nm = Proxy::new(conn, NM, NM_PATH, NM)?;
+    let active: Vec<zbus::zvariant::OwnedObjectPath> = nm.get_property("ActiveConnections")?;
+    for path in active {
+        let connection = Proxy::new(conn, NM, path, NM_ACTIVE)?;
+        let kind: String = connection.get_property("Type")?;
+        let vpn: bool = connection.get_property("Vpn")?;
+        let state: u32 = connection.get_property("State")?;
+        if (vpn || kind == "wireguard") && state == NM_ACTIVATED {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn tailscale() -> Result<bool, String> {
+    #[derive(Deserialize)]
+    struct Status {
+        #[serde(rename = "BackendState")]
+        backend_state: String,
+    }
+
+    let out = cmd("tailscale", &["status", "--json"])?;
+    let status: Status =
+        serde_json::from_str(&out).map_err(|err| format!("Invalid tailscale status: {}", err))?;
+    Ok(status.backend_state == "Running")
+}
@@ -0,0 +1,180 @@
+use serde::Deserialize;
+
+use super::Rgba;
+use crate::config::Colors;
+
+/// A level and the color to use from it on, as configured.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stop {
+    level: f64,
+
+    /// A palette name, or `#rrggbb(aa)`.
+    color: String,
+}
+
+impl Stop {
+    pub fn new(level: f64, color: &str) -> Self {
+        Stop {
+            level,
+            color: color.into(),
+        }
+    }
+}
+
+/// Which side of its level each stop's color is used on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// At or below it, e.g. for a draining battery.
+    Falling,
+
+    /// At or above it, e.g. for a rising temperature.
+    Rising,
+}
+
+/// Maps a level (e.g. a charge) to a color, either stepping at each
+/// stop or blending between neighbouring ones.
+#[derive(Debug)]
+pub struct Ramp {
+    /// Sorted in the order they're reached, i.e. by
+    /// ascending level when falling and descending when rising.
+    stops: Vec<(f64, Rgba)>,
+    direction: Direction,
+    interpolate: bool,
+}
+
+impl Ramp {
+    pub fn new(mut stops: Vec<(f64, Rgba)>, direction: Direction, interpolate: bool) -> Self {
+        stops.sort_by(|(a, _), (b, _)| match direction {
+            Direction::Falling => a.total_cmp(b),
+            Direction::Rising => b.total_cmp(a),
+        });
+        Ramp {
+            stops,
+            direction,
+            interpolate,
+        }
+    }
+
+    /// A ramp from configured stops, with their colors looked up in the palette.
+    pub fn parse(
+        stops: &[Stop],
+        direction: Direction,
+        interpolate: bool,
+        colors: &Colors,
+    ) -> Result<Self, String> {
+        let stops = stops
+            .iter()
+            .map(|stop| {
+                let color = colors
+                    .resolve(&stop.color)
+                    .map_err(|err| format!("Invalid level: {}", err))?;
+                Ok((stop.level, color))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Ramp::new(stops, direction, interpolate))
+    }
+
+    /// The color of the nearest stop `level` has reached,
+    /// or `otherwise` if it hasn't reached any of them.
+    /// Colors are only blended between stops, never towards `otherwise`.
+    pub fn color(&self, level: f64, otherwise: Rgba) -> Rgba {
+        let reached = |stop: f64| match self.direction {
+            Direction::Falling => level <= stop,
+            Direction::Rising => level >= stop,
+        };
+        let Some(index) = self.stops.iter().position(|(stop, _)| reached(*stop)) else {
+            return otherwise;
+        };
+        let (to, color) = self.stops[index];
+        if !self.interpolate || index == 0 {
+            return color;
+        }
+        let (from, from_color) = self.stops[index - 1];
+        let t = (level - from) / (to - from);
+        let mut blended = from_color;
+        for (channel, to) in blended.iter_mut().zip(color) {
+            *channel += (to - *channel) * t;
+        }
+        blended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba = [0., 0., 0., 1.];
+    const GREY: Rgba = [0.5, 0.5, 0.5, 1.];
+    const WHITE: Rgba = [1., 1., 1., 1.];
+    const RED: Rgba = [1., 0., 0., 1.];
+
+    fn falling(interpolate: bool) -> Ramp {
+        Ramp::new(
+            vec![(0.5, WHITE), (0.25, BLACK)],
+            Direction::Falling,
+            interpolate,
+        )
+    }
+
+    fn rising(interpolate: bool) -> Ramp {
+        Ramp::new(
+            vec![(70., WHITE), (90., BLACK)],
+            Direction::Rising,
+            interpolate,
+        )
+    }
+
+    #[test]
+    fn steps_while_falling() {
+        let ramp = falling(false);
+        assert_eq!(ramp.color(0.1, RED), BLACK);
+        assert_eq!(ramp.color(0.25, RED), BLACK);
+        assert_eq!(ramp.color(0.3, RED), WHITE);
+        assert_eq!(ramp.color(0.5, RED), WHITE);
+    }
+
+    #[test]
+    fn steps_while_rising() {
+        let ramp = rising(false);
+        assert_eq!(ramp.color(95., RED), BLACK);
+        assert_eq!(ramp.color(90., RED), BLACK);
+        assert_eq!(ramp.color(80., RED), WHITE);
+        assert_eq!(ramp.color(70., RED), WHITE);
+    }
+
+    #[test]
+    fn uses_fallback_before_any_stop() {
+        assert_eq!(falling(false).color(0.6, RED), RED);
+        assert_eq!(falling(true).color(0.6, RED), RED);
+        assert_eq!(rising(false).color(60., RED), RED);
+        assert_eq!(rising(true).color(60., RED), RED);
+    }
+
+    #[test]
+    fn interpolates_between_stops() {
+        let ramp = falling(true);
+        assert_eq!(ramp.color(0.5, RED), WHITE);
+        assert_eq!(ramp.color(0.375, RED), GREY);
+        assert_eq!(ramp.color(0.25, RED), BLACK);
+        assert_eq!(ramp.color(0.1, RED), BLACK);
+
+        let ramp = rising(true);
+        assert_eq!(ramp.color(70., RED), WHITE);
+        assert_eq!(ramp.color(80., RED), GREY);
+        assert_eq!(ramp.color(90., RED), BLACK);
+        assert_eq!(ramp.color(95., RED), BLACK);
+    }
+
+    #[test]
+    fn resolves_configured_colors() {
+        let colors = Colors::default();
+        let stops = [Stop::new(0.2, "#ffffff"), Stop::new(0.1, "urgent")];
+        let ramp = Ramp::parse(&stops, Direction::Falling, false, &colors).unwrap();
+        assert_eq!(ramp.color(0.05, RED), colors.urgent);
+        assert_eq!(ramp.color(0.15, RED), WHITE);
+
+        let stops = [Stop::new(0.1, "purple")];
+        assert!(Ramp::parse(&stops, Direction::Falling, false, &colors).is_err());
+    }
+}
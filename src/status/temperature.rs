@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{
+    ramp::{Direction, Ramp},
+    Bar, Indicator,
+};
+use crate::config::Colors;
+
+const SYSFS: &str = "/sys";
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Sensor to show, by hwmon label (e.g. `Package id 0`), hwmon
+    /// chip name (e.g. `k10temp`) or thermal zone type (e.g.
+    /// `x86_pkg_temp`). The hottest sensor by default.
+    sensor: Option<String>,
+
+    /// Temperatures shown as an empty and a full bar, in °C.
+    floor: f64,
+    ceiling: f64,
+
+    /// Temperature at or above which the bar turns to a warning.
+    warn: f64,
+
+    /// Temperature at or above which the bar turns urgent.
+    urgent: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            sensor: None,
+            floor: 30.,
+            ceiling: 100.,
+            warn: 70.,
+            urgent: 85.,
+        }
+    }
+}
+
+/// A temperature sensor.
+#[derive(Debug, Clone)]
+struct Sensor {
+    /// Names the sensor can be selected by, most specific first.
+    names: Vec<String>,
+
+    /// In °C.
+    temperature: f64,
+}
+
+/// A bar representing the temperature of a sensor.
+pub struct Temperature {
+    colors: Colors,
+    options: Options,
+    levels: Ramp,
+    sensor: Option<Sensor>,
+}
+
+impl Temperature {
+    pub fn new(colors: Colors, options: Options) -> Result<Self, String> {
+        if options.ceiling <= options.floor {
+            return Err(format!(
+                "Temperature ceiling {} should be above the floor {}",
+                options.ceiling, options.floor
+            ));
+        }
+        let levels = Ramp::new(
+            vec![(options.warn, colors.warn), (options.urgent, colors.urgent)],
+            Direction::Rising,
+            false,
+        );
+        Ok(Temperature {
+            colors,
+            options,
+            levels,
+            sensor: None,
+        })
+    }
+}
+
+impl Indicator for Temperature {
+    fn name(&self) -> &'static str {
+        "temperature"
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let sensors = sensors(Path::new(SYSFS));
+        self.sensor = select(sensors, self.options.sensor.as_deref());
+        match (&self.options.sensor, &self.sensor) {
+            (Some(name), None) => Err(format!("No temperature sensor named {}", name)),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&self) -> Option<Bar> {
+        let temperature = self.sensor.as_ref()?.temperature;
+        let Options { floor, ceiling, .. } = self.options;
+        let percent = ((temperature - floor) / (ceiling - floor)).clamp(0., 1.);
+        Some((percent, self.levels.color(temperature, self.colors.normal)))
+    }
+
+    fn details(&self) -> Option<String> {
+        let sensor = self.sensor.as_ref()?;
+        Some(format!(
+            "{:.0}°C ({})",
+            sensor.temperature,
+            sensor.names.join(", ")
+        ))
+    }
+}
+
+/// List the hwmon sensors and thermal zones.
+fn sensors(root: &Path) -> Vec<Sensor> {
+    let mut sensors = vec![];
+    for chip in entries(&root.join("class/hwmon")) {
+        let chip_name = read(&chip.join("name"));
+        for input in entries(&chip) {
+            let Some(file) = input.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(prefix) = file
+                .strip_suffix("_input")
+                .filter(|prefix| prefix.starts_with("temp"))
+            else {
+                continue;
+            };
+            let Some(millis) = read(&input).and_then(|value| value.parse::<f64>().ok()) else {
+                continue;
+            };
+            let label = read(&chip.join(format!("{}_label", prefix)));
+            sensors.push(Sensor {
+                names: label.into_iter().chain(chip_name.clone()).collect(),
+                temperature: millis / 1000.,
+            });
+        }
+    }
+    for zone in entries(&root.join("class/thermal")) {
+        let is_zone = zone
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("thermal_zone"));
+        let temperature = read(&zone.join("temp")).and_then(|value| value.parse::<f64>().ok());
+        if let (true, Some(millis)) = (is_zone, temperature) {
+            sensors.push(Sensor {
+                names: read(&zone.join("type")).into_iter().collect(),
+                temperature: millis / 1000.,
+            });
+        }
+    }
+    sensors
+}
+
+/// The sensor with the given name, or the hottest one.
+fn select(sensors: Vec<Sensor>, name: Option<&str>) -> Option<Sensor> {
+    match name {
+        Some(name) => sensors
+            .into_iter()
+            .find(|sensor| sensor.names.iter().any(|names| names == name)),
+        None => sensors
+            .into_iter()
+            .max_by(|a, b| a.temperature.total_cmp(&b.temperature)),
+    }
+}
+
+/// The paths in a directory, sorted so sensors are found in a stable order.
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn read(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(contents.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sysfs tree with two hwmon chips and a thermal zone.
+    fn sysfs() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let files = [
+            ("class/hwmon/hwmon0/name", "coretemp\n"),
+            ("class/hwmon/hwmon0/temp1_input", "55000\n"),
+            ("class/hwmon/hwmon0/temp1_label", "Package id 0\n"),
+            ("class/hwmon/hwmon0/temp2_input", "61000\n"),
+            ("class/hwmon/hwmon0/temp2_label", "Core 0\n"),
+            ("class/hwmon/hwmon0/fan1_input", "2000\n"),
+            ("class/hwmon/hwmon1/name", "nvme\n"),
+            ("class/hwmon/hwmon1/temp1_input", "40000\n"),
+            ("class/thermal/thermal_zone0/type", "x86_pkg_temp\n"),
+            ("class/thermal/thermal_zone0/temp", "72000\n"),
+            ("class/thermal/cooling_device0/type", "Processor\n"),
+        ];
+        for (path, contents) in files {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    fn named(root: &Path, name: &str) -> Option<f64> {
+        Some(select(sensors(root), Some(name))?.temperature)
+    }
+
+    #[test]
+    fn lists_sensors() {
+        let root = sysfs();
+        let sensors: Vec<_> = sensors(root.path())
+            .into_iter()
+            .map(|sensor| (sensor.names, sensor.temperature))
+            .collect();
+        assert_eq!(
+            sensors,
+            [
+                (vec!["Package id 0".into(), "coretemp".into()], 55.),
+                (vec!["Core 0".into(), "coretemp".into()], 61.),
+                (vec!["nvme".into()], 40.),
+                (vec!["x86_pkg_temp".into()], 72.),
+            ]
+        );
+    }
+
+    #[test]
+    fn selects_by_name() {
+        let root = sysfs();
+        assert_eq!(named(root.path(), "Core 0"), Some(61.));
+        // The first sensor of the chip.
+        assert_eq!(named(root.path(), "coretemp"), Some(55.));
+        assert_eq!(named(root.path(), "x86_pkg_temp"), Some(72.));
+        assert_eq!(named(root.path(), "k10temp"), None);
+    }
+
+    #[test]
+    fn selects_hottest_by_default() {
+        let root = sysfs();
+        let sensor = select(sensors(root.path()), None).unwrap();
+        assert_eq!(sensor.temperature, 72.);
+    }
+
+    #[test]
+    fn rejects_empty_range() {
+        let options = Options {
+            floor: 50.,
+            ceiling: 50.,
+            ..Options::default()
+        };
+        assert!(Temperature::new(Colors::default(), options).is_err());
+    }
+
+    #[test]
+    fn forgets_vanished_sensor() {
+        let options = Options {
+            sensor: Some("no such sensor".into()),
+            ..Options::default()
+        };
+        let mut temperature = Temperature::new(Colors::default(), options).unwrap();
+        temperature.sensor = Some(Sensor {
+            names: vec!["no such sensor".into()],
+            temperature: 50.,
+        });
+        assert!(temperature.poll().is_err());
+        assert!(temperature.value().is_none());
+    }
+}